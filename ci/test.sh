@@ -4,8 +4,10 @@ main() {
     . ./ci/preamble.sh
     cargo fmt --all --check
     cargo clippy --quiet --all-targets --all-features --workspace -- -D warnings
-    cargo test --workspace --lib -- --nocapture
-    cargo test --workspace --test '*' -- --nocapture
+    cargo test --workspace --all-features --lib -- --nocapture
+    cargo test --workspace --all-features --test '*' -- --nocapture
+    cargo build --package elb --no-default-features
+    cargo test --package elb --no-default-features --test no_std -- --nocapture
 }
 
 main
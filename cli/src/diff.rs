@@ -0,0 +1,54 @@
+use colored::Colorize;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use elb::Elf;
+use elb::ElfChange;
+use elb::ElfDiff;
+use elb::ElfSummary;
+use fs_err::File;
+
+use crate::CommonArgs;
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// Old ELF file.
+    #[clap(value_name = "old ELF file")]
+    old: PathBuf,
+
+    /// New ELF file.
+    #[clap(value_name = "new ELF file")]
+    new: PathBuf,
+}
+
+pub fn diff(common: CommonArgs, args: DiffArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let old = read_summary(&args.old, common.page_size)?;
+    let new = read_summary(&args.new, common.page_size)?;
+    let diff = ElfDiff::new(&old, &new);
+    let mut writer = BufWriter::new(std::io::stdout());
+    for change in diff.changes().iter() {
+        let line = change.to_string();
+        let line = match change {
+            ElfChange::SectionAdded(..)
+            | ElfChange::SegmentAdded(..)
+            | ElfChange::DynamicAdded(..)
+            | ElfChange::SymbolAdded { .. } => line.green(),
+            ElfChange::SectionRemoved(..)
+            | ElfChange::SegmentRemoved(..)
+            | ElfChange::DynamicRemoved(..)
+            | ElfChange::SymbolRemoved { .. } => line.red(),
+            _ => line.yellow(),
+        };
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_summary(path: &Path, page_size: u64) -> Result<ElfSummary, Box<dyn std::error::Error>> {
+    let mut file = File::open(path)?;
+    let elf = Elf::read_unchecked(&mut file, page_size)?;
+    Ok(ElfSummary::read(&elf, &mut file)?)
+}
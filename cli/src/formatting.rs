@@ -218,6 +218,7 @@ impl std::fmt::Display for DynamicTagStr {
             DynamicTag::RelrTableAddress => Some("RELR"),
             DynamicTag::RelrTableSize => Some("RELRSZ"),
             DynamicTag::RelrEntrySize => Some("RELRENT"),
+            DynamicTag::FLAGS_1 => Some("FLAGS_1"),
             DynamicTag::Other(0x6ffffef5) => Some("GNU_HASH"),
             DynamicTag::Other(0x6ffffffe) => Some("VERNEED"),
             DynamicTag::Other(0x6fffffff) => Some("VERNEEDNUM"),
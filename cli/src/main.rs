@@ -8,18 +8,22 @@ use elb::Elf;
 use fs_err::File;
 
 mod deps;
+mod diff;
 mod formatting;
 mod logger;
 mod patch;
 mod relocate;
 mod show;
+mod strip;
 
 use self::deps::*;
+use self::diff::*;
 use self::formatting::*;
 use self::logger::*;
 use self::patch::*;
 use self::relocate::*;
 use self::show::*;
+use self::strip::*;
 
 #[derive(clap::Parser)]
 #[clap(version)]
@@ -50,7 +54,11 @@ enum Command {
     /// Relocate ELF with all of its dependencies.
     Relocate(RelocateArgs),
     /// Modify ELF file.
-    Patch(PatchArgs),
+    Patch(Box<PatchArgs>),
+    /// Show structural differences between two ELF files.
+    Diff(DiffArgs),
+    /// Remove debug information and symbols.
+    Strip(StripArgs),
 }
 
 #[derive(clap::Args)]
@@ -78,7 +86,9 @@ fn do_main() -> Result<(), Box<dyn std::error::Error>> {
         Command::Check { file } => check(args.common, file),
         Command::Deps(deps_args) => deps(args.common, deps_args),
         Command::Relocate(relocate_args) => relocate(args.common, relocate_args),
-        Command::Patch(patch_args) => patch(args.common, patch_args),
+        Command::Patch(patch_args) => patch(args.common, *patch_args),
+        Command::Diff(diff_args) => diff(args.common, diff_args),
+        Command::Strip(strip_args) => strip(args.common, strip_args),
     }
 }
 
@@ -1,13 +1,24 @@
 use clap::ValueEnum;
-use std::ffi::CStr;
+use std::collections::BTreeMap;
 use std::ffi::CString;
-use std::ffi::OsString;
 use std::os::unix::ffi::OsStringExt;
-use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 
+use elb::ArmFlags;
+use elb::DryRun;
 use elb::Elf;
 use elb::ElfPatcher;
+use elb::ElfRead;
+use elb::ElfSeek;
+use elb::ElfTruncate;
+use elb::ElfWrite;
+use elb::Machine;
+use elb::MachineFlags;
+use elb::PatchTransaction;
+use elb::RiscvFlags;
+use elb::SectionFlags;
+use elb::SectionKind;
+use elb::SymbolBinding;
 use fs_err as fs;
 
 use crate::CommonArgs;
@@ -30,53 +41,168 @@ pub struct PatchArgs {
     #[clap(action, long = "remove-dynamic", value_name = "tag")]
     remove_dynamic: Vec<DynamicEntry>,
 
+    /// Add NEEDED entry before the existing ones.
+    #[clap(long = "add-needed", value_name = "library")]
+    add_needed: Vec<PathBuf>,
+
+    /// Remove NEEDED entry.
+    #[clap(long = "remove-needed", value_name = "library")]
+    remove_needed: Vec<PathBuf>,
+
+    /// Replace NEEDED entry preserving the order of the entries.
+    #[clap(long = "replace-needed", value_name = "old=new")]
+    replace_needed: Vec<String>,
+
+    /// Rename dynamic symbol; use the same renames for all the libraries that use the symbol.
+    #[clap(long = "rename-dynamic-symbol", value_name = "old=new")]
+    rename_dynamic_symbol: Vec<String>,
+
+    /// Make symbol local.
+    #[clap(long = "localize-symbol", value_name = "name")]
+    localize_symbol: Vec<String>,
+
+    /// Make symbol global.
+    #[clap(long = "globalize-symbol", value_name = "name")]
+    globalize_symbol: Vec<String>,
+
+    /// Make symbol weak.
+    #[clap(long = "weaken-symbol", value_name = "name")]
+    weaken_symbol: Vec<String>,
+
+    /// Set symbol visibility; supports default, internal, hidden, protected.
+    #[clap(long = "set-symbol-visibility", value_name = "name=visibility")]
+    set_symbol_visibility: Vec<String>,
+
+    /// Mark the stack as executable.
+    #[clap(action, long = "set-execstack", conflicts_with = "clear_execstack")]
+    set_execstack: bool,
+
+    /// Mark the stack as non-executable.
+    #[clap(action, long = "clear-execstack")]
+    clear_execstack: bool,
+
+    /// Set the requested stack size.
+    #[clap(long = "set-stack-size", value_name = "NUM")]
+    set_stack_size: Option<u64>,
+
+    /// Set OS ABI; similar to `brandelf -t`.
+    #[clap(long = "set-os-abi", value_name = "abi")]
+    set_os_abi: Option<OsAbi>,
+
+    /// Set ABI version.
+    #[clap(long = "set-abi-version", value_name = "NUM")]
+    set_abi_version: Option<u8>,
+
+    /// Set the entry point address; should be covered by LOAD segment.
+    #[clap(long = "set-entry-point", value_name = "address", value_parser = parse_address)]
+    set_entry_point: Option<u64>,
+
+    /// Set architecture-specific header flag; supports ARM and RISC-V flags.
+    #[clap(long = "set-flag", value_name = "name")]
+    set_flag: Vec<String>,
+
+    /// Clear architecture-specific header flag.
+    #[clap(long = "clear-flag", value_name = "name")]
+    clear_flag: Vec<String>,
+
+    /// Add non-loadable section with the contents of the file.
+    #[clap(long = "add-section", value_name = "name=file")]
+    add_section: Vec<String>,
+
+    /// Add read-only loadable section with the contents of the file.
+    #[clap(long = "add-loadable-section", value_name = "name=file")]
+    add_loadable_section: Vec<String>,
+
+    /// The alignment of the added sections; defaults to the word size for loadable sections
+    /// and to 1 for the other sections.
+    #[clap(long = "section-align", value_name = "NUM", value_parser = parse_address)]
+    section_align: Option<u64>,
+
+    /// Remove all sections with the specified name.
+    #[clap(long = "remove-section", value_name = "name")]
+    remove_section: Vec<String>,
+
+    /// Add or replace `.gnu_debuglink` section that points to the debug information file.
+    #[clap(long = "add-debuglink", value_name = "file")]
+    add_debuglink: Option<PathBuf>,
+
+    /// How to replace the interpreter and dynamic table strings.
+    #[clap(long = "policy", value_name = "policy", default_value = "relocate")]
+    policy: Policy,
+
+    /// Print the changes instead of modifying the file.
+    #[clap(action, long = "dry-run")]
+    dry_run: bool,
+
+    /// Reclaim the space freed by the other modifications and truncate the file.
+    #[clap(action, long = "compact")]
+    compact: bool,
+
     /// ELF file.
     #[clap(value_name = "ELF file")]
     file: PathBuf,
 }
 
 pub fn patch(common: CommonArgs, args: PatchArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let elf = Elf::read(&mut fs::File::open(&args.file)?, common.page_size)?;
+    let path = args.file.clone();
+    let compact = args.compact;
+    if args.dry_run {
+        let mut file = DryRun::new(fs::File::open(&path)?.into_file());
+        let elf = Elf::read(&mut file, common.page_size)?;
+        let mut patcher = ElfPatcher::new(elf, file);
+        let changed = apply(&mut patcher, args)?;
+        let mut file = finish(patcher, compact, changed)?;
+        let plan = file.plan(common.page_size)?;
+        print!("{}", plan);
+        return Ok(());
+    }
+    let mut transaction = PatchTransaction::open(&path, common.page_size)?;
+    let changed = apply(&mut transaction, args)?;
+    if compact {
+        transaction.compact()?;
+        transaction.commit_and_truncate()?;
+    } else {
+        check_changed(changed)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// Apply the modifications from the command line.
+///
+/// Returns `true` if there were any modifications.
+fn apply<F: ElfRead + ElfWrite + ElfSeek>(
+    patcher: &mut ElfPatcher<F>,
+    args: PatchArgs,
+) -> Result<bool, Box<dyn std::error::Error>> {
     let mut changed = false;
-    let file_name = args.file.file_name().expect("File name exists");
-    let new_file_name = {
-        let mut name = OsString::new();
-        name.push(".");
-        name.push(file_name);
-        name.push(".tmp");
-        name
-    };
-    let new_path = match args.file.parent() {
-        Some(parent) => parent.join(&new_file_name),
-        None => new_file_name.into(),
-    };
-    let _ = std::fs::remove_file(&new_path);
-    fs::copy(&args.file, &new_path)?;
-    // Make writable.
-    let mut permissions = fs::metadata(&new_path)?.permissions();
-    let old_permissions = permissions.clone();
-    permissions.set_mode(0o200 | permissions.mode());
-    fs::set_permissions(&new_path, permissions)?;
-    let file = fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(&new_path)?;
-    let mut patcher = ElfPatcher::new(elf, file);
+    patcher.set_patch_policy(args.policy.into());
     if args.remove_interpreter {
         patcher.remove_interpreter()?;
         changed = true;
     } else if let Some(path) = args.set_interpreter {
-        let os_string = path.into_os_string();
-        let mut bytes = os_string.into_vec();
-        bytes.push(0_u8);
-        let c_str = CStr::from_bytes_with_nul(&bytes)?;
-        patcher.set_interpreter(c_str)?;
+        patcher.set_interpreter(&path_to_c_string(path)?)?;
         changed = true;
     }
     for entry in args.remove_dynamic.into_iter() {
         patcher.remove_dynamic_tag(entry.into())?;
         changed = true;
     }
+    for library in args.remove_needed.into_iter() {
+        patcher.remove_needed(&path_to_c_string(library)?)?;
+        changed = true;
+    }
+    for pair in args.replace_needed.into_iter() {
+        let mut iter = pair.splitn(2, '=');
+        let old = iter.next().ok_or("Old library not found")?;
+        let new = iter.next().ok_or("New library not found")?;
+        patcher.replace_needed(&CString::new(old)?, &CString::new(new)?)?;
+        changed = true;
+    }
+    for (i, library) in args.add_needed.into_iter().enumerate() {
+        patcher.add_needed(&path_to_c_string(library)?, i)?;
+        changed = true;
+    }
     for pair in args.set_dynamic.into_iter() {
         let mut iter = pair.splitn(2, '=');
         let tag: DynamicEntry = ValueEnum::from_str(iter.next().ok_or("Tag not found")?, true)?;
@@ -86,16 +212,136 @@ pub fn patch(common: CommonArgs, args: PatchArgs) -> Result<(), Box<dyn std::err
         patcher.set_dynamic_tag(tag.into(), value.as_c_str())?;
         changed = true;
     }
+    if !args.rename_dynamic_symbol.is_empty() {
+        let mut mapping = BTreeMap::new();
+        for pair in args.rename_dynamic_symbol.into_iter() {
+            let mut iter = pair.splitn(2, '=');
+            let old = iter.next().ok_or("Old symbol name not found")?;
+            let new = iter.next().ok_or("New symbol name not found")?;
+            mapping.insert(CString::new(old)?, CString::new(new)?);
+        }
+        patcher.rename_dynamic_symbols(&mapping)?;
+        changed = true;
+    }
+    if args.set_execstack || args.clear_execstack {
+        patcher.set_executable_stack(args.set_execstack)?;
+        changed = true;
+    }
+    if let Some(size) = args.set_stack_size {
+        patcher.set_stack_size(size)?;
+        changed = true;
+    }
+    if let Some(os_abi) = args.set_os_abi {
+        patcher.set_os_abi(os_abi.into())?;
+        changed = true;
+    }
+    if let Some(abi_version) = args.set_abi_version {
+        patcher.set_abi_version(abi_version)?;
+        changed = true;
+    }
+    if let Some(entry_point) = args.set_entry_point {
+        patcher.set_entry_point(entry_point)?;
+        changed = true;
+    }
+    for (names, value) in [(args.set_flag, true), (args.clear_flag, false)] {
+        for name in names.into_iter() {
+            match patcher.elf().header.machine {
+                Machine::Arm => patcher.set_flags(parse_flag::<ArmFlags>(&name)?, value)?,
+                Machine::Riscv => patcher.set_flags(parse_flag::<RiscvFlags>(&name)?, value)?,
+                machine => return Err(format!("Unsupported header flags: {machine:?}").into()),
+            }
+            changed = true;
+        }
+    }
+    for (names, binding) in [
+        (args.localize_symbol, SymbolBinding::Local),
+        (args.globalize_symbol, SymbolBinding::Global),
+        (args.weaken_symbol, SymbolBinding::Weak),
+    ] {
+        for name in names.into_iter() {
+            patcher.set_symbol_binding(&CString::new(name)?, binding)?;
+            changed = true;
+        }
+    }
+    for pair in args.set_symbol_visibility.into_iter() {
+        let mut iter = pair.splitn(2, '=');
+        let name = iter.next().ok_or("Symbol name not found")?;
+        let visibility: Visibility =
+            ValueEnum::from_str(iter.next().ok_or("Visibility not found")?, true)?;
+        patcher.set_symbol_visibility(&CString::new(name)?, visibility.into())?;
+        changed = true;
+    }
+    for name in args.remove_section.into_iter() {
+        patcher.remove_section(&CString::new(name)?)?;
+        changed = true;
+    }
+    let word_len = patcher.elf().header.class.word_len() as u64;
+    for (pair, flags, default_align) in args
+        .add_section
+        .into_iter()
+        .map(|pair| (pair, SectionFlags::empty(), 1))
+        .chain(
+            args.add_loadable_section
+                .into_iter()
+                .map(|pair| (pair, SectionFlags::ALLOC, word_len)),
+        )
+    {
+        let mut iter = pair.splitn(2, '=');
+        let name = iter.next().ok_or("Section name not found")?;
+        let path = iter.next().ok_or("File not found")?;
+        let content = fs::read(path)?;
+        patcher.add_section(
+            &CString::new(name)?,
+            SectionKind::ProgramBits,
+            flags,
+            args.section_align.unwrap_or(default_align),
+            &content,
+        )?;
+        changed = true;
+    }
+    if let Some(path) = args.add_debuglink {
+        patcher.set_debuglink(path)?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+fn finish<F: ElfRead + ElfWrite + ElfSeek + ElfTruncate>(
+    mut patcher: ElfPatcher<F>,
+    compact: bool,
+    changed: bool,
+) -> Result<F, Box<dyn std::error::Error>> {
+    if compact {
+        patcher.compact()?;
+        return Ok(patcher.finish_and_truncate()?);
+    }
+    check_changed(changed)?;
+    Ok(patcher.finish()?)
+}
+
+fn check_changed(changed: bool) -> Result<(), Box<dyn std::error::Error>> {
     if !changed {
         return Err("No changes".into());
     }
-    patcher.finish()?;
-    // Restore original file permissions.
-    fs::set_permissions(&new_path, old_permissions)?;
-    fs::rename(&new_path, &args.file)?;
     Ok(())
 }
 
+fn parse_address(s: &str) -> Result<u64, std::num::ParseIntError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+fn parse_flag<T: MachineFlags>(name: &str) -> Result<T, Box<dyn std::error::Error>> {
+    T::from_name(&name.to_uppercase().replace('-', "_"))
+        .ok_or_else(|| format!("Unknown flag: {name}").into())
+}
+
+fn path_to_c_string(path: PathBuf) -> Result<CString, Box<dyn std::error::Error>> {
+    Ok(CString::new(path.into_os_string().into_vec())?)
+}
+
 #[derive(clap::ValueEnum, Clone, Copy)]
 #[clap(rename_all = "SCREAMING_SNAKE_CASE")]
 enum DynamicEntry {
@@ -113,3 +359,82 @@ impl From<DynamicEntry> for elb::DynamicTag {
         }
     }
 }
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum Policy {
+    /// Always write the new value to a new location.
+    Relocate,
+    /// Overwrite the old value if the new one fits.
+    PreferInPlace,
+    /// Overwrite the old value or fail.
+    InPlaceOnly,
+}
+
+impl From<Policy> for elb::PatchPolicy {
+    fn from(other: Policy) -> Self {
+        match other {
+            Policy::Relocate => Self::Relocate,
+            Policy::PreferInPlace => Self::PreferInPlace,
+            Policy::InPlaceOnly => Self::InPlaceOnly,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum Visibility {
+    Default,
+    Internal,
+    Hidden,
+    Protected,
+}
+
+impl From<Visibility> for elb::SymbolVisibility {
+    fn from(other: Visibility) -> Self {
+        match other {
+            Visibility::Default => Self::Default,
+            Visibility::Internal => Self::Internal,
+            Visibility::Hidden => Self::Hidden,
+            Visibility::Protected => Self::Protected,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum OsAbi {
+    Sysv,
+    Hpux,
+    Netbsd,
+    #[clap(alias = "linux")]
+    Gnu,
+    Solaris,
+    Aix,
+    Irix,
+    Freebsd,
+    Tru64,
+    Modesto,
+    Openbsd,
+    ArmAeabi,
+    Arm,
+    Standalone,
+}
+
+impl From<OsAbi> for elb::OsAbi {
+    fn from(other: OsAbi) -> Self {
+        match other {
+            OsAbi::Sysv => Self::Sysv,
+            OsAbi::Hpux => Self::Hpux,
+            OsAbi::Netbsd => Self::Netbsd,
+            OsAbi::Gnu => Self::Gnu,
+            OsAbi::Solaris => Self::Solaris,
+            OsAbi::Aix => Self::Aix,
+            OsAbi::Irix => Self::Irix,
+            OsAbi::Freebsd => Self::Freebsd,
+            OsAbi::Tru64 => Self::Tru64,
+            OsAbi::Modesto => Self::Modesto,
+            OsAbi::Openbsd => Self::Openbsd,
+            OsAbi::ArmAeabi => Self::ArmAeabi,
+            OsAbi::Arm => Self::Arm,
+            OsAbi::Standalone => Self::Standalone,
+        }
+    }
+}
@@ -6,6 +6,8 @@ use std::path::PathBuf;
 
 use elb::ArmFlags;
 use elb::BlockRead;
+use elb::DynamicFlags;
+use elb::DynamicFlags1;
 use elb::DynamicTag;
 use elb::Elf;
 use elb::ElfSeek;
@@ -250,6 +252,14 @@ fn show_dynamic(
             | DynamicTag::PreInitArraySize => {
                 printer.row(format_args!("{tag_str:20}  {value} B"));
             }
+            DynamicTag::Flags => {
+                let flags = DynamicFlags::from_bits_retain(value);
+                printer.row(format_args!("{tag_str:20}  {flags:?}"));
+            }
+            DynamicTag::FLAGS_1 => {
+                let flags = DynamicFlags1::from_bits_retain(value);
+                printer.row(format_args!("{tag_str:20}  {flags:?}"));
+            }
             // Counts.
             DynamicTag::Other(0x6ffffff9) | DynamicTag::Other(0x6fffffff) => {
                 printer.row(format_args!("{tag_str:20}  {value}"));
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use elb::Cursor;
+use elb::Elf;
+use elb::ElfPatcher;
+use elb::PatchTransaction;
+use fs_err as fs;
+
+use crate::CommonArgs;
+
+#[derive(clap::Args)]
+pub struct StripArgs {
+    /// Remove debug sections only.
+    #[clap(short = 'g', long = "strip-debug", conflicts_with = "strip_all")]
+    strip_debug: bool,
+
+    /// Remove debug sections and the symbol table (default).
+    #[clap(short = 's', long = "strip-all")]
+    strip_all: bool,
+
+    /// Write the debug file instead of stripping.
+    #[clap(
+        long = "only-keep-debug",
+        conflicts_with_all = ["strip_debug", "strip_all"]
+    )]
+    only_keep_debug: bool,
+
+    /// Output file. By default the input file is modified in place.
+    #[clap(short = 'o', long = "output", value_name = "file")]
+    output: Option<PathBuf>,
+
+    /// ELF file.
+    #[clap(value_name = "ELF file")]
+    file: PathBuf,
+}
+
+pub fn strip(common: CommonArgs, args: StripArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let output = args.output.as_ref().unwrap_or(&args.file);
+    if args.only_keep_debug {
+        let elf = Elf::read(&mut fs::File::open(&args.file)?, common.page_size)?;
+        let mut patcher = ElfPatcher::new(elf, fs::File::open(&args.file)?);
+        let mut debug_file = Cursor::new(Vec::new());
+        patcher.write_debug_file(&mut debug_file)?;
+        if output == &args.file {
+            elb::replace_file(output, debug_file.get_ref())?;
+        } else {
+            // Don't inherit the permissions and the capabilities of the executable.
+            fs::write(output, debug_file.get_ref())?;
+        }
+        return Ok(());
+    }
+    if output != &args.file {
+        elb::copy_file(&args.file, output)?;
+    }
+    let mut transaction = PatchTransaction::open(output, common.page_size)?;
+    if args.strip_debug {
+        transaction.strip_debug()?;
+    } else {
+        transaction.strip_all()?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
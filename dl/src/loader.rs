@@ -114,6 +114,32 @@ impl Default for DependencyTree {
     }
 }
 
+/// Serialized as a map from the dependent to its dependencies.
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl serde::Serialize for DependencyTree {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.dependencies
+                .iter()
+                .map(|(dependent, dependencies)| (dependent, dependencies)),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for DependencyTree {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The map is sorted by the dependent's path which is what binary search expects.
+        let dependencies: std::collections::BTreeMap<PathBuf, Vec<PathBuf>> =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            dependencies: dependencies.into_iter().collect(),
+        })
+    }
+}
+
 impl IntoIterator for DependencyTree {
     type Item = (PathBuf, Vec<PathBuf>);
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -10,7 +10,7 @@ use std::path::PathBuf;
 
 use elb::DynamicTag;
 use elb::Elf;
-use elb::ElfPatcher;
+use elb::PatchTransaction;
 
 use crate::fs;
 use crate::fs::os::unix::fs::symlink;
@@ -98,7 +98,7 @@ fn relocate_file(file: &Path, dir: &Path) -> Result<(Hash, PathBuf), Error> {
     fs::create_dir_all(&new_path)?;
     new_path.push(file.file_name().expect("File name exists"));
     let _ = std::fs::remove_file(&new_path);
-    fs::copy(file, &new_path)?;
+    elb::copy_file(file, &new_path)?;
     Ok((hash, new_path))
 }
 
@@ -133,9 +133,9 @@ fn patch_file(file: &Path, directory: &Path, hash: &Hash, page_size: u64) -> Res
         // Don't patch statically-linked executables.
         return Ok(());
     }
-    let mut file = fs::OpenOptions::new().read(true).write(true).open(file)?;
-    let elf = Elf::read(&mut file, page_size)?;
-    let mut patcher = ElfPatcher::new(elf, file);
+    let mut patcher = PatchTransaction::open(file, page_size)?;
+    // The file is a fresh copy, we only need atomicity here.
+    patcher.set_sync(false);
     if let Some(old_interpreter) = patcher.read_interpreter()? {
         let interpreter = {
             let old_interpreter = Path::new(OsStr::from_bytes(old_interpreter.to_bytes()));
@@ -163,7 +163,7 @@ fn patch_file(file: &Path, directory: &Path, hash: &Hash, page_size: u64) -> Res
         unsafe { CString::from_vec_with_nul_unchecked(bytes) }
     };
     patcher.set_dynamic_tag(DynamicTag::Runpath, runpath.as_c_str())?;
-    patcher.finish()?;
+    patcher.commit()?;
     Ok(())
 }
 
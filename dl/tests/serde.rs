@@ -0,0 +1,28 @@
+#![allow(missing_docs)]
+#![cfg(feature = "serde")]
+
+use elb_dl::DependencyTree;
+
+#[test]
+fn dependency_tree_round_trip() {
+    let mut tree = DependencyTree::new();
+    tree.insert("/bin/b".into(), vec!["/lib/libc.so.6".into()]);
+    tree.insert(
+        "/bin/a".into(),
+        vec!["/lib/libm.so.6".into(), "/lib/libc.so.6".into()],
+    );
+    let json = serde_json::to_value(&tree).unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "/bin/a": ["/lib/libm.so.6", "/lib/libc.so.6"],
+            "/bin/b": ["/lib/libc.so.6"],
+        }),
+        json
+    );
+    let tree2: DependencyTree = serde_json::from_value(json).unwrap();
+    assert!(tree2.contains(std::path::Path::new("/bin/a")));
+    assert_eq!(
+        tree.iter().collect::<Vec<_>>(),
+        tree2.iter().collect::<Vec<_>>()
+    );
+}
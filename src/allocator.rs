@@ -144,13 +144,18 @@ impl<'a> SpaceAllocator<'a> {
 
     /// Allocate in-file and in-memory space for the specified `ALLOC` section.
     ///
+    /// `NOBITS` sections are allocated in-memory only: either at the end of compatible `LOAD`
+    /// segment (by growing its memory size) or in a new `LOAD` segment with zero in-file size.
+    ///
     /// On success sets [`Section::offset`] and [`Section::virtual_address`].
+    /// Fails with [`Error::InvalidSectionKind`] if the section is `NULL` or is not `ALLOC`.
     pub fn allocate_section(mut self, section: &mut Section) -> Result<(), Error> {
+        if !section.flags.contains(SectionFlags::ALLOC) || section.kind == SectionKind::Null {
+            return Err(Error::InvalidSectionKind(section.kind.as_u32()));
+        }
         if section.kind == SectionKind::NoBits {
-            // TODO handle NoBits
-            unimplemented!("Allocating NOBITS sections is not implemented");
+            return self.allocate_nobits_section(section);
         }
-        assert!(section.flags.contains(SectionFlags::ALLOC) && section.kind != SectionKind::Null);
         let (offset_from_start, segment_index) = self
             .allocate_space(&self.file_events, section)
             .or_else(|| {
@@ -169,15 +174,65 @@ impl<'a> SpaceAllocator<'a> {
         Ok(())
     }
 
+    fn allocate_nobits_section(mut self, section: &mut Section) -> Result<(), Error> {
+        let align = section.align.max(1);
+        if let Some((virtual_address, i)) = self.allocate_memory_space_at_segment_end(section) {
+            let segment = &mut self.segments[i];
+            segment.memory_size = virtual_address + section.size - segment.virtual_address;
+            section.virtual_address = virtual_address;
+            // The section isn't stored in the file, the offset is arbitrary.
+            section.offset = segment.offset + segment.file_size;
+            return Ok(());
+        }
+        // We didn't find sufficient free space after existing segments, let's add a new segment.
+        let (offset_from_start, segment_index) = self
+            .allocate_loadable_segment_for(0, section.size, align, segment_flags_for(section.flags))
+            .ok_or(Error::SectionAlloc)?;
+        let segment = &mut self.segments[segment_index];
+        // Padding isn't stored in the file.
+        segment.file_size = 0;
+        section.offset = segment.offset + offset_from_start;
+        section.virtual_address = segment.virtual_address + offset_from_start;
+        Ok(())
+    }
+
+    /// Find compatible `LOAD` segment that can be extended in memory to fit the section.
+    ///
+    /// Returns the virtual address of the section and the index of the segment.
+    fn allocate_memory_space_at_segment_end(&self, section: &Section) -> Option<(u64, usize)> {
+        let align = section.align.max(1);
+        for (i, segment) in self.segments.iter().enumerate() {
+            if segment.kind != SegmentKind::Loadable || !segment.is_compatible_with(section) {
+                continue;
+            }
+            let end = segment.virtual_address.checked_add(segment.memory_size)?;
+            let start = end.checked_next_multiple_of(align)?;
+            let new_end = start.checked_add(section.size)?;
+            // `ld.so` expands the segment to the page boundary.
+            let limit = new_end.checked_next_multiple_of(self.page_size)?;
+            let vacant = self.memory_events.iter().all(|event| {
+                let own =
+                    event.index == i && matches!(event.kind, LoadSegmentStart | LoadSegmentEnd);
+                let is_start = matches!(event.kind, LoadSegmentStart | SectionStart);
+                own || !(event.offset > end || is_start && event.offset == end)
+                    || event.offset >= limit
+            });
+            if vacant {
+                return Some((start, i));
+            }
+        }
+        None
+    }
+
     /// Allocate in-file and in-memory space for the specified `LOAD` segment.
     ///
     /// On success sets [`Segment::offset`], [`Segment::virtual_address`] and
     /// [`Segment::physical_address`].
+    /// Fails with [`Error::InvalidSegmentKind`] if the segment is `LOAD` or `NULL`.
     pub fn allocate_segment(mut self, segment: &mut Segment) -> Result<(), Error> {
-        assert!(!matches!(
-            segment.kind,
-            SegmentKind::Loadable | SegmentKind::Null
-        ));
+        if matches!(segment.kind, SegmentKind::Loadable | SegmentKind::Null) {
+            return Err(Error::InvalidSegmentKind(segment.kind.as_u32()));
+        }
         let (offset_from_start, segment_index) = self
             .allocate_loadable_segment_for(
                 segment.file_size,
@@ -193,6 +248,22 @@ impl<'a> SpaceAllocator<'a> {
         Ok(())
     }
 
+    /// Allocate new `LOAD` segment at the end of the file and at the end of the memory.
+    ///
+    /// The segment is appended to the list of segments.
+    /// Returns the offset from the start of the segment that satisfies the alignment and the
+    /// index of the new segment.
+    pub fn allocate_loadable_segment(
+        mut self,
+        file_size: u64,
+        memory_size: u64,
+        align: u64,
+        flags: SegmentFlags,
+    ) -> Result<(u64, usize), Error> {
+        self.allocate_loadable_segment_for(file_size, memory_size, align, flags)
+            .ok_or(Error::SegmentAlloc)
+    }
+
     fn allocate_loadable_segment_for(
         &mut self,
         file_size: u64,
@@ -451,6 +522,8 @@ impl Segment {
         (self.kind == SegmentKind::Loadable) == section.flags.contains(SectionFlags::ALLOC)
             && self.flags.contains(SegmentFlags::WRITABLE)
                 == section.flags.contains(SectionFlags::WRITE)
+            && (!section.flags.contains(SectionFlags::EXECUTABLE)
+                || self.flags.contains(SegmentFlags::EXECUTABLE))
     }
 }
 
@@ -459,6 +532,9 @@ fn segment_flags_for(section_flags: SectionFlags) -> SegmentFlags {
     if section_flags.contains(SectionFlags::WRITE) {
         flags.insert(SegmentFlags::WRITABLE);
     }
+    if section_flags.contains(SectionFlags::EXECUTABLE) {
+        flags.insert(SegmentFlags::EXECUTABLE);
+    }
     flags
 }
 
@@ -487,7 +563,13 @@ mod tests {
     use alloc::vec;
     use arbtest::arbtest;
 
+    use crate::ByteOrder;
     use crate::Class;
+    use crate::FileKind;
+    use crate::Header;
+    use crate::Machine;
+    use crate::OsAbi;
+    use crate::ProgramHeader;
 
     #[test]
     fn test_align_down() {
@@ -650,6 +732,114 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_allocate_nobits_section() {
+        let header = header();
+        // Extend writable segment in memory.
+        {
+            let sections = vec![];
+            let mut segments = ProgramHeader::default();
+            segments.push(memory_segment(0x1000, 0x100, SegmentFlags::WRITABLE));
+            let alloc = SpaceAllocator::new(Class::Elf64, 4096, &sections, &mut segments);
+            let mut section = nobits_section(0x200, 16);
+            alloc.allocate_section(&mut section).unwrap();
+            assert_eq!(0x1100, section.virtual_address);
+            assert_eq!(1, segments.len());
+            assert_eq!(0x100, segments[0].file_size);
+            assert_eq!(0x300, segments[0].memory_size);
+            segments.check(&header, 4096).unwrap();
+        }
+        // Add new segment if the next segment is too close.
+        {
+            let sections = vec![];
+            let mut segments = ProgramHeader::default();
+            segments.push(memory_segment(0x1000, 0x100, SegmentFlags::WRITABLE));
+            segments.push(memory_segment(0x2000, 0x100, SegmentFlags::READABLE));
+            let alloc = SpaceAllocator::new(Class::Elf64, 4096, &sections, &mut segments);
+            let mut section = nobits_section(0x2000, 16);
+            alloc.allocate_section(&mut section).unwrap();
+            assert_eq!(3, segments.len());
+            assert_eq!(0x100, segments[0].memory_size);
+            assert_eq!(0, segments[2].file_size);
+            assert!(segments[2].memory_size >= 0x2000);
+            assert_eq!(segments[2].virtual_address, section.virtual_address);
+            segments.check(&header, 4096).unwrap();
+        }
+        // Add new writable segment if there are only read-only segments.
+        {
+            let sections = vec![];
+            let mut segments = ProgramHeader::default();
+            segments.push(memory_segment(0x1000, 0x100, SegmentFlags::READABLE));
+            let alloc = SpaceAllocator::new(Class::Elf64, 4096, &sections, &mut segments);
+            let mut section = nobits_section(0x100, 16);
+            alloc.allocate_section(&mut section).unwrap();
+            assert_eq!(2, segments.len());
+            assert!(segments[1].flags.contains(SegmentFlags::WRITABLE));
+            assert_eq!(0, segments[1].file_size);
+            segments.check(&header, 4096).unwrap();
+        }
+        // NULL and non-ALLOC sections are rejected.
+        {
+            let sections = vec![];
+            let mut segments = ProgramHeader::default();
+            let mut section = section(0x100, 1, SectionFlags::ALLOC);
+            section.kind = SectionKind::Null;
+            let alloc = SpaceAllocator::new(Class::Elf64, 4096, &sections, &mut segments);
+            assert!(matches!(
+                alloc.allocate_section(&mut section),
+                Err(Error::InvalidSectionKind(0))
+            ));
+            section.kind = SectionKind::ProgramBits;
+            section.flags = SectionFlags::empty();
+            let alloc = SpaceAllocator::new(Class::Elf64, 4096, &sections, &mut segments);
+            assert!(matches!(
+                alloc.allocate_section(&mut section),
+                Err(Error::InvalidSectionKind(1))
+            ));
+        }
+    }
+
+    fn header() -> Header {
+        Header {
+            class: Class::Elf64,
+            byte_order: ByteOrder::LittleEndian,
+            os_abi: OsAbi::Sysv,
+            abi_version: 0,
+            kind: FileKind::Executable,
+            machine: Machine::X86_64,
+            flags: 0,
+            entry_point: 0,
+            program_header_offset: 0,
+            segment_len: 0,
+            num_segments: 0,
+            section_header_offset: 0,
+            section_len: 0,
+            num_sections: 0,
+            section_names_index: 0,
+            len: 0,
+        }
+    }
+
+    fn memory_segment(virtual_address: u64, size: u64, flags: SegmentFlags) -> Segment {
+        Segment {
+            kind: SegmentKind::Loadable,
+            flags,
+            offset: virtual_address,
+            virtual_address,
+            physical_address: virtual_address,
+            file_size: size,
+            memory_size: size,
+            align: 4096,
+        }
+    }
+
+    fn nobits_section(size: u64, align: u64) -> Section {
+        Section {
+            kind: SectionKind::NoBits,
+            ..section(size, align, SectionFlags::WRITE | SectionFlags::ALLOC)
+        }
+    }
+
     fn file_section(offset: u64, size: u64, flags: SectionFlags) -> Section {
         Section {
             name_offset: 0,
@@ -0,0 +1,264 @@
+use alloc::ffi::CString;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+use crate::constants::*;
+use crate::BlockWrite;
+use crate::ByteOrder;
+use crate::Class;
+use crate::ElfBuilder;
+use crate::ElfSeek;
+use crate::ElfWrite;
+use crate::Error;
+use crate::FileKind;
+use crate::Machine;
+use crate::Section;
+use crate::SectionFlags;
+use crate::SectionKind;
+use crate::StringTable;
+use crate::Symbol;
+use crate::SymbolBinding;
+use crate::SymbolKind;
+use crate::SymbolTable;
+use crate::SymbolVisibility;
+
+/// Relocatable object file that embeds a binary blob.
+///
+/// Produces the same symbols as `ld -r -b binary` and `objcopy -I binary`:
+/// `{name}_start` and `{name}_end` mark the boundaries of the blob,
+/// `{name}_size` is an absolute symbol which value is the size of the blob.
+#[derive(Debug)]
+pub struct BlobObject {
+    class: Class,
+    byte_order: ByteOrder,
+    machine: Machine,
+    flags: u32,
+    symbol_name: CString,
+    section_name: CString,
+    section_flags: SectionFlags,
+    align: u64,
+}
+
+impl BlobObject {
+    /// Create new object for the specified target and symbol name prefix.
+    ///
+    /// By default the blob is placed into read-only `.rodata` section without alignment.
+    pub fn new(class: Class, byte_order: ByteOrder, machine: Machine, symbol_name: &CStr) -> Self {
+        Self {
+            class,
+            byte_order,
+            machine,
+            flags: 0,
+            symbol_name: symbol_name.into(),
+            section_name: RODATA_SECTION.into(),
+            section_flags: SectionFlags::ALLOC,
+            align: 1,
+        }
+    }
+
+    /// Set architecture-specific flags.
+    ///
+    /// Some linkers refuse to link objects with incompatible flags, e.g. ARM EABI version or
+    /// RISC-V floating-point ABI.
+    pub fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+    }
+
+    /// Set the name of the section that contains the blob.
+    pub fn set_section_name(&mut self, name: &CStr) {
+        self.section_name = name.into();
+    }
+
+    /// Set the flags of the section that contains the blob.
+    pub fn set_section_flags(&mut self, flags: SectionFlags) {
+        self.section_flags = flags;
+    }
+
+    /// Set the alignment of the blob.
+    pub fn set_align(&mut self, align: u64) {
+        self.align = align;
+    }
+
+    /// Write relocatable object file that contains `blob` to `writer`.
+    pub fn write<W: ElfWrite + ElfSeek>(&self, writer: &mut W, blob: &[u8]) -> Result<(), Error> {
+        let mut builder = ElfBuilder::new(
+            self.class,
+            self.byte_order,
+            self.machine,
+            FileKind::Relocatable,
+        );
+        builder.set_flags(self.flags);
+        let blob_len = blob.len() as u64;
+        // The section indices are known in advance.
+        let blob_index: u16 = 1;
+        let strings_index: u32 = 3;
+        let mut strings = StringTable::new();
+        let mut symbols = SymbolTable::new();
+        // Local symbols go first.
+        symbols.push(Symbol {
+            address: 0,
+            size: 0,
+            name_offset: 0,
+            section_index: Symbol::UNDEFINED,
+            binding: SymbolBinding::Local,
+            kind: SymbolKind::None,
+            visibility: SymbolVisibility::Default,
+        });
+        symbols.push(Symbol {
+            address: 0,
+            size: 0,
+            name_offset: 0,
+            section_index: blob_index,
+            binding: SymbolBinding::Local,
+            kind: SymbolKind::Section,
+            visibility: SymbolVisibility::Default,
+        });
+        let num_local_symbols = symbols.len() as u32;
+        for (suffix, address, section_index) in [
+            (c"_start", 0, blob_index),
+            (c"_end", blob_len, blob_index),
+            (c"_size", blob_len, Symbol::ABSOLUTE),
+        ] {
+            let mut name: Vec<u8> = self.symbol_name.to_bytes().into();
+            name.extend_from_slice(suffix.to_bytes());
+            let name = CString::new(name).map_err(|_| Error::CStr)?;
+            let name_offset = strings
+                .insert(&name)
+                .try_into()
+                .map_err(|_| Error::TooBig("Symbol name offset"))?;
+            symbols.push(Symbol {
+                address,
+                size: 0,
+                name_offset,
+                section_index,
+                binding: SymbolBinding::Global,
+                kind: SymbolKind::None,
+                visibility: SymbolVisibility::Default,
+            });
+        }
+        let i = builder.add_section(
+            &self.section_name,
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: self.section_flags,
+                align: self.align,
+                ..Default::default()
+            },
+            blob.into(),
+        );
+        debug_assert_eq!(blob_index as usize, i);
+        let mut symbols_content = Vec::new();
+        symbols.write(&mut symbols_content, self.class, self.byte_order)?;
+        builder.add_section(
+            SYMTAB_SECTION,
+            Section {
+                kind: SectionKind::SymbolTable,
+                flags: SectionFlags::empty(),
+                link: strings_index,
+                // The index of the first non-local symbol.
+                info: num_local_symbols,
+                align: self.class.word_len() as u64,
+                entry_len: self.class.symbol_len() as u64,
+                ..Default::default()
+            },
+            symbols_content,
+        );
+        let i = builder.add_section(
+            STRTAB_SECTION,
+            Section {
+                kind: SectionKind::StringTable,
+                flags: SectionFlags::empty(),
+                align: STRING_TABLE_ALIGN,
+                ..Default::default()
+            },
+            strings.as_bytes().into(),
+        );
+        debug_assert_eq!(strings_index as usize, i);
+        // Mark the stack as non-executable.
+        builder.add_section(
+            GNU_STACK_SECTION,
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::empty(),
+                align: 1,
+                ..Default::default()
+            },
+            Vec::new(),
+        );
+        builder.write(writer)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::host;
+    use crate::Elf;
+    use fs_err::File;
+    use std::process::Command;
+
+    #[test]
+    fn link_blob() {
+        let (Some(class), Some(byte_order), Some(machine)) =
+            (host::CLASS, host::BYTE_ORDER, host::MACHINE)
+        else {
+            return;
+        };
+        let workdir = tempfile::tempdir().unwrap();
+        let blob: Vec<u8> = (0..=255_u8).cycle().take(1000).collect();
+        let object_path = workdir.path().join("blob.o");
+        let mut object = BlobObject::new(class, byte_order, machine, c"test_blob");
+        object.set_align(16);
+        let mut file = File::create(&object_path).unwrap();
+        object.write(&mut file, &blob).unwrap();
+        drop(file);
+        let mut file = File::open(&object_path).unwrap();
+        let elf = Elf::read(&mut file, DEFAULT_PAGE_SIZE).unwrap();
+        assert_eq!(FileKind::Relocatable, elf.header.kind);
+        drop(file);
+        let main_path = workdir.path().join("main.c");
+        fs_err::write(
+            &main_path,
+            r#"
+#include <stdio.h>
+#include <stdint.h>
+extern const unsigned char test_blob_start[];
+extern const unsigned char test_blob_end[];
+extern const unsigned char test_blob_size[];
+int main() {
+    size_t n = (size_t)(test_blob_end - test_blob_start);
+    if (n != (size_t)(uintptr_t)test_blob_size) { return 1; }
+    if ((uintptr_t)test_blob_start % 16 != 0) { return 2; }
+    for (size_t i = 0; i < n; ++i) {
+        if (test_blob_start[i] != (unsigned char)i) { return 3; }
+    }
+    printf("%zu\n", n);
+    return 0;
+}
+"#,
+        )
+        .unwrap();
+        let exe_path = workdir.path().join("main");
+        // Absolute `_size` symbol can't be referenced from position-independent code,
+        // the same applies to the objects produced by `objcopy`.
+        let status = match Command::new("cc")
+            .arg("-no-pie")
+            .arg("-o")
+            .arg(&exe_path)
+            .arg(&main_path)
+            .arg(&object_path)
+            .status()
+        {
+            Ok(status) => status,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::eprintln!("`cc` not found, skipping the test");
+                return;
+            }
+            Err(e) => panic!("Failed to run `cc`: {e}"),
+        };
+        assert!(status.success());
+        let output = Command::new(&exe_path).output().unwrap();
+        assert_eq!(Some(0), output.status.code());
+        assert_eq!(b"1000\n", &output.stdout[..]);
+    }
+}
@@ -0,0 +1,552 @@
+use alloc::ffi::CString;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+use crate::align_down;
+use crate::constants::*;
+use crate::ByteOrder;
+use crate::Class;
+use crate::Elf;
+use crate::ElfSeek;
+use crate::ElfWrite;
+use crate::Error;
+use crate::FileKind;
+use crate::Header;
+use crate::Machine;
+use crate::OsAbi;
+use crate::ProgramHeader;
+use crate::Section;
+use crate::SectionFlags;
+use crate::SectionHeader;
+use crate::SectionKind;
+use crate::Segment;
+use crate::SegmentFlags;
+use crate::SegmentKind;
+use crate::SpaceAllocator;
+use crate::StringTable;
+
+/// ELF builder.
+///
+/// Creates ELF files from scratch.
+/// Segments and sections are laid out with [`SpaceAllocator`],
+/// section names table, program header and section header are generated automatically.
+#[derive(Debug)]
+pub struct ElfBuilder {
+    header: Header,
+    page_size: u64,
+    base_address: u64,
+    segments: Vec<(Segment, Vec<u8>)>,
+    sections: Vec<(CString, Section, SectionContent)>,
+    entry_point: Option<(usize, u64)>,
+}
+
+impl ElfBuilder {
+    /// Create new builder with the specified header fields.
+    ///
+    /// Base address defaults to `0x400000` for executables and to zero for any other file kind.
+    pub fn new(class: Class, byte_order: ByteOrder, machine: Machine, kind: FileKind) -> Self {
+        let base_address = if kind == FileKind::Executable {
+            DEFAULT_EXECUTABLE_BASE_ADDRESS
+        } else {
+            0
+        };
+        Self {
+            header: Header {
+                class,
+                byte_order,
+                os_abi: OsAbi::Sysv,
+                abi_version: 0,
+                kind,
+                machine,
+                flags: 0,
+                entry_point: 0,
+                program_header_offset: 0,
+                segment_len: 0,
+                num_segments: 0,
+                section_header_offset: 0,
+                section_len: 0,
+                num_sections: 0,
+                section_names_index: 0,
+                len: class.header_len(),
+            },
+            page_size: DEFAULT_PAGE_SIZE,
+            base_address,
+            segments: Default::default(),
+            sections: Default::default(),
+            entry_point: None,
+        }
+    }
+
+    /// Set operating system ABI.
+    pub fn set_os_abi(&mut self, os_abi: OsAbi) {
+        self.header.os_abi = os_abi;
+    }
+
+    /// Set operating system ABI version.
+    pub fn set_abi_version(&mut self, abi_version: u8) {
+        self.header.abi_version = abi_version;
+    }
+
+    /// Set architecture-specific flags.
+    pub fn set_flags(&mut self, flags: u32) {
+        self.header.flags = flags;
+    }
+
+    /// Set page size.
+    ///
+    /// Page size is used to align `LOAD` segments.
+    pub fn set_page_size(&mut self, page_size: u64) {
+        self.page_size = page_size;
+    }
+
+    /// Set the virtual address of the first `LOAD` segment.
+    ///
+    /// Should be a multiple of page size.
+    pub fn set_base_address(&mut self, base_address: u64) {
+        self.base_address = base_address;
+    }
+
+    /// Add new segment with the specified contents.
+    ///
+    /// Offset, addresses and in-file size of the segment are computed automatically.
+    /// In-memory size is set to the size of the contents if it is smaller.
+    /// Non-`LOAD` segments with non-empty contents are covered by new `LOAD` segment.
+    ///
+    /// Returns the index of the segment that can be used in
+    /// [`add_segment_section`](Self::add_segment_section) and
+    /// [`set_entry_point`](Self::set_entry_point).
+    pub fn add_segment(&mut self, mut segment: Segment, content: Vec<u8>) -> usize {
+        segment.file_size = content.len() as u64;
+        segment.memory_size = segment.memory_size.max(segment.file_size);
+        let i = self.segments.len();
+        self.segments.push((segment, content));
+        i
+    }
+
+    /// Add new section with the specified contents.
+    ///
+    /// Offset, virtual address, size and name offset of the section are computed automatically.
+    /// `ALLOC` sections are placed into `LOAD` segments unless the file is relocatable.
+    ///
+    /// Returns the index of the section in the section header.
+    pub fn add_section(&mut self, name: &CStr, mut section: Section, content: Vec<u8>) -> usize {
+        if section.kind != SectionKind::NoBits {
+            section.size = content.len() as u64;
+        }
+        self.do_add_section(name, section, SectionContent::Own(content))
+    }
+
+    /// Add new section that occupies the part of the specified segment.
+    ///
+    /// The section starts at `offset` bytes from the start of the segment's contents and has
+    /// [`Section::size`] bytes.
+    ///
+    /// Returns the index of the section in the section header.
+    pub fn add_segment_section(
+        &mut self,
+        segment: usize,
+        offset: u64,
+        name: &CStr,
+        section: Section,
+    ) -> usize {
+        self.do_add_section(name, section, SectionContent::Segment(segment, offset))
+    }
+
+    /// Set the entry point to the address `offset` bytes from the start of the segment's contents.
+    pub fn set_entry_point(&mut self, segment: usize, offset: u64) {
+        self.entry_point = Some((segment, offset));
+    }
+
+    /// Lay out segments and sections, validate and write the file to `writer`.
+    pub fn write<W: ElfWrite + ElfSeek>(self, writer: &mut W) -> Result<(), Error> {
+        if !self.page_size.is_power_of_two() {
+            return Err(Error::InvalidAlign(self.page_size));
+        }
+        if align_down(self.base_address, self.page_size) != self.base_address {
+            return Err(Error::InvalidAlign(self.base_address));
+        }
+        let class = self.header.class;
+        let byte_order = self.header.byte_order;
+        let relocatable = self.header.kind == FileKind::Relocatable;
+        // Section names.
+        let mut names = StringTable::new();
+        let mut name_offsets = Vec::with_capacity(self.sections.len());
+        for (name, ..) in self.sections.iter() {
+            name_offsets.push(name_offset(names.insert(name))?);
+        }
+        let names_name_offset = name_offset(names.insert(SHSTRTAB_SECTION))?;
+        let mut segments = ProgramHeader::default();
+        let mut sections = SectionHeader::default();
+        sections.push(Section::null());
+        // Segments.
+        let mut segment_starts = Vec::with_capacity(self.segments.len());
+        for (segment, _content) in self.segments.iter() {
+            let mut segment = segment.clone();
+            let start = if segment.kind == SegmentKind::Loadable {
+                let alloc = SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+                let (offset_from_start, i) = alloc.allocate_loadable_segment(
+                    segment.file_size,
+                    segment.memory_size,
+                    segment.align,
+                    segment.flags,
+                )?;
+                let outer = &segments[i];
+                (
+                    outer.offset + offset_from_start,
+                    outer.virtual_address + offset_from_start,
+                )
+            } else {
+                if segment.file_size != 0 || segment.memory_size != 0 {
+                    let alloc =
+                        SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+                    alloc.allocate_segment(&mut segment)?;
+                }
+                let start = (segment.offset, segment.virtual_address);
+                segments.add(segment);
+                start
+            };
+            segment_starts.push(start);
+        }
+        // Standalone `ALLOC` sections go first to not overwrite segments' contents.
+        // Other sections are replaced by NULL sections that the allocator ignores.
+        let allocate_in_memory = |section: &Section| {
+            !relocatable
+                && section.flags.contains(SectionFlags::ALLOC)
+                && section.kind != SectionKind::Null
+        };
+        for (_, section, content) in self.sections.iter() {
+            let mut section = section.clone();
+            if allocate_in_memory(&section) && matches!(content, SectionContent::Own(..)) {
+                let alloc = SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+                alloc.allocate_section(&mut section)?;
+                sections.push(section);
+            } else {
+                sections.push(Section::null());
+            }
+        }
+        for (i, ((_, section, content), name_offset)) in
+            self.sections.iter().zip(name_offsets).enumerate()
+        {
+            let i = i + 1;
+            let mut section = section.clone();
+            section.name_offset = name_offset;
+            match content {
+                SectionContent::Own(..) if allocate_in_memory(&section) => {
+                    section.offset = sections[i].offset;
+                    section.virtual_address = sections[i].virtual_address;
+                }
+                SectionContent::Own(..) => {
+                    let alloc =
+                        SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+                    let file_size = if section.kind == SectionKind::NoBits {
+                        0
+                    } else {
+                        section.size
+                    };
+                    section.offset = alloc
+                        .allocate_file_space(file_size, section.align)
+                        .ok_or(Error::FileSpaceAlloc)?;
+                    section.virtual_address = 0;
+                }
+                SectionContent::Segment(j, offset) => {
+                    let (start, virtual_address) = *segment_starts
+                        .get(*j)
+                        .ok_or(Error::InvalidSegmentIndex(*j))?;
+                    section.offset = start + offset;
+                    section.virtual_address = if section.flags.contains(SectionFlags::ALLOC) {
+                        virtual_address + offset
+                    } else {
+                        0
+                    };
+                }
+            }
+            sections[i] = section;
+        }
+        // Section names.
+        let alloc = SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+        let names_len = names.as_bytes().len() as u64;
+        let names_offset = alloc
+            .allocate_file_space(names_len, STRING_TABLE_ALIGN)
+            .ok_or(Error::FileSpaceAlloc)?;
+        let section_names_index = sections.len();
+        sections.push(Section {
+            name_offset: names_name_offset,
+            kind: SectionKind::StringTable,
+            flags: SectionFlags::empty(),
+            virtual_address: 0,
+            offset: names_offset,
+            size: names_len,
+            link: 0,
+            info: 0,
+            align: STRING_TABLE_ALIGN,
+            entry_len: 0,
+        });
+        // Program header.
+        let mut header = self.header;
+        if !segments.is_empty() {
+            let program_header_len = (segments.len() as u64)
+                // +1 because PHDR is also a segment
+                // +1 because PHDR segment has to be covered by LOAD segment
+                .checked_add(2)
+                .ok_or(Error::TooBig("No. of segments"))?
+                .checked_mul(class.segment_len() as u64)
+                .ok_or(Error::TooBig("No. of segments"))?;
+            let mut phdr = Segment {
+                kind: SegmentKind::ProgramHeader,
+                flags: SegmentFlags::READABLE,
+                offset: 0,
+                virtual_address: 0,
+                physical_address: 0,
+                file_size: program_header_len,
+                memory_size: program_header_len,
+                align: PHDR_ALIGN,
+            };
+            let alloc = SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+            alloc.allocate_segment(&mut phdr)?;
+            header.program_header_offset = phdr.offset;
+            segments.add(phdr);
+            header.segment_len = class.segment_len();
+        }
+        // Section header.
+        let section_header_len = (sections.len() as u64)
+            .checked_mul(class.section_len() as u64)
+            .ok_or(Error::TooBig("No. of sections"))?;
+        let alloc = SpaceAllocator::new(class, self.page_size, &sections, &mut segments);
+        header.section_header_offset = alloc
+            .allocate_file_space(section_header_len, SECTION_HEADER_ALIGN)
+            .ok_or(Error::FileSpaceAlloc)?;
+        header.section_len = class.section_len();
+        header.num_sections = sections
+            .len()
+            .try_into()
+            .map_err(|_| Error::TooBig("No. of sections"))?;
+        header.num_segments = segments
+            .len()
+            .try_into()
+            .map_err(|_| Error::TooBig("No. of segments"))?;
+        header.section_names_index = section_names_index
+            .try_into()
+            .map_err(|_| Error::TooBig("No. of sections"))?;
+        // Move everything to the base address.
+        if !relocatable {
+            for segment in segments.iter_mut() {
+                segment.virtual_address = segment
+                    .virtual_address
+                    .checked_add(self.base_address)
+                    .ok_or(Error::TooBig("Base address"))?;
+                segment.physical_address = segment.virtual_address;
+            }
+            for section in sections.iter_mut() {
+                if section.flags.contains(SectionFlags::ALLOC) {
+                    section.virtual_address = section
+                        .virtual_address
+                        .checked_add(self.base_address)
+                        .ok_or(Error::TooBig("Base address"))?;
+                }
+            }
+        }
+        if let Some((i, offset)) = self.entry_point {
+            let (_, virtual_address) =
+                *segment_starts.get(i).ok_or(Error::InvalidSegmentIndex(i))?;
+            header.entry_point = virtual_address
+                .checked_add(self.base_address)
+                .and_then(|address| address.checked_add(offset))
+                .ok_or(Error::TooBig("Entry point"))?;
+        }
+        segments.finish();
+        // Write the contents.
+        for ((_, content), (offset, _)) in self.segments.iter().zip(segment_starts.iter()) {
+            writer.seek(*offset)?;
+            writer.write_bytes(content)?;
+        }
+        for (i, (_, _, content)) in self.sections.iter().enumerate() {
+            let section = &sections[i + 1];
+            if let SectionContent::Own(content) = content {
+                if section.kind != SectionKind::NoBits {
+                    section.write_content(writer, class, byte_order, content)?;
+                }
+            }
+        }
+        sections[section_names_index].write_content(writer, class, byte_order, &names)?;
+        let elf = Elf::new(header, segments, sections, self.page_size);
+        elf.write(writer)
+    }
+
+    fn do_add_section(&mut self, name: &CStr, section: Section, content: SectionContent) -> usize {
+        self.sections.push((name.into(), section, content));
+        // +1 because of the NULL section.
+        self.sections.len()
+    }
+}
+
+#[derive(Debug)]
+enum SectionContent {
+    Own(Vec<u8>),
+    Segment(usize, u64),
+}
+
+fn name_offset(offset: usize) -> Result<u32, Error> {
+    offset
+        .try_into()
+        .map_err(|_| Error::TooBig("Section name offset"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs_err::File;
+    use fs_err::OpenOptions;
+
+    #[test]
+    fn build_relocatable() {
+        let mut builder = ElfBuilder::new(
+            Class::Elf64,
+            ByteOrder::LittleEndian,
+            Machine::X86_64,
+            FileKind::Relocatable,
+        );
+        let i = builder.add_section(
+            c".rodata",
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::ALLOC,
+                align: 16,
+                ..Default::default()
+            },
+            b"hello".to_vec(),
+        );
+        assert_eq!(1, i);
+        let mut cursor = crate::Cursor::new(Vec::new());
+        builder.write(&mut cursor).unwrap();
+        let elf = Elf::read(&mut cursor, DEFAULT_PAGE_SIZE).unwrap();
+        assert!(elf.segments.is_empty());
+        let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+        let content = elf.read_section(c".rodata", &names, &mut cursor).unwrap();
+        assert_eq!(Some(b"hello".to_vec()), content);
+        assert_eq!(0, elf.sections[1].offset % 16);
+    }
+
+    #[test]
+    fn invalid_segment_index() {
+        let new_builder = || {
+            ElfBuilder::new(
+                Class::Elf64,
+                ByteOrder::LittleEndian,
+                Machine::X86_64,
+                FileKind::Executable,
+            )
+        };
+        let mut builder = new_builder();
+        builder.set_entry_point(3, 0);
+        let mut cursor = crate::Cursor::new(Vec::new());
+        assert!(matches!(
+            builder.write(&mut cursor),
+            Err(Error::InvalidSegmentIndex(3))
+        ));
+        let mut builder = new_builder();
+        builder.add_segment_section(
+            1,
+            0,
+            c".text",
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::ALLOC | SectionFlags::EXECUTABLE,
+                align: 1,
+                size: 1,
+                ..Default::default()
+            },
+        );
+        let mut cursor = crate::Cursor::new(Vec::new());
+        assert!(matches!(
+            builder.write(&mut cursor),
+            Err(Error::InvalidSegmentIndex(1))
+        ));
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn build_and_run_executable() {
+        use fs_err::os::unix::fs::OpenOptionsExt;
+        use std::process::Command;
+
+        let mut builder = ElfBuilder::new(
+            Class::Elf64,
+            ByteOrder::LittleEndian,
+            Machine::X86_64,
+            FileKind::Executable,
+        );
+        let code = [
+            0xbf, 0x2a, 0x00, 0x00, 0x00, // mov edi, 42
+            0xb8, 0x3c, 0x00, 0x00, 0x00, // mov eax, 60 (exit)
+            0x0f, 0x05, // syscall
+        ];
+        let text = builder.add_segment(
+            Segment {
+                kind: SegmentKind::Loadable,
+                flags: SegmentFlags::READABLE | SegmentFlags::EXECUTABLE,
+                offset: 0,
+                virtual_address: 0,
+                physical_address: 0,
+                file_size: 0,
+                memory_size: 0,
+                align: DEFAULT_PAGE_SIZE,
+            },
+            code.to_vec(),
+        );
+        builder.add_segment_section(
+            text,
+            0,
+            c".text",
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::ALLOC | SectionFlags::EXECUTABLE,
+                size: code.len() as u64,
+                align: 1,
+                ..Default::default()
+            },
+        );
+        builder.add_section(
+            c".rodata",
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::ALLOC,
+                align: 8,
+                ..Default::default()
+            },
+            b"read-only data".to_vec(),
+        );
+        builder.add_section(
+            c".comment",
+            Section {
+                kind: SectionKind::ProgramBits,
+                flags: SectionFlags::empty(),
+                align: 1,
+                ..Default::default()
+            },
+            b"elb\0".to_vec(),
+        );
+        builder.set_entry_point(text, 0);
+        let workdir = tempfile::tempdir().unwrap();
+        let path = workdir.path().join("exit42");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .mode(0o755)
+            .open(&path)
+            .unwrap();
+        builder.write(&mut file).unwrap();
+        drop(file);
+        let mut file = File::open(&path).unwrap();
+        let elf = Elf::read(&mut file, DEFAULT_PAGE_SIZE).unwrap();
+        assert_eq!(DEFAULT_EXECUTABLE_BASE_ADDRESS, elf.header.entry_point);
+        let names = elf.read_section_names(&mut file).unwrap().unwrap();
+        assert_eq!(
+            Some(b"elb\0".to_vec()),
+            elf.read_section(c".comment", &names, &mut file).unwrap()
+        );
+        drop(file);
+        let status = Command::new(&path).status().unwrap();
+        assert_eq!(Some(42), status.code());
+    }
+}
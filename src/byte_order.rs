@@ -1,3 +1,4 @@
+use crate::define_enum_serde;
 use crate::Error;
 
 /// Data format (endiannes).
@@ -11,6 +12,8 @@ pub enum ByteOrder {
     BigEndian = 2,
 }
 
+define_enum_serde!(ByteOrder, u8, LittleEndian, BigEndian,);
+
 impl TryFrom<u8> for ByteOrder {
     type Error = Error;
     fn try_from(other: u8) -> Result<Self, Self::Error> {
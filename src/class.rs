@@ -1,4 +1,5 @@
 use crate::constants::*;
+use crate::define_enum_serde;
 use crate::Error;
 
 /// Architecture.
@@ -12,6 +13,8 @@ pub enum Class {
     Elf64 = 2,
 }
 
+define_enum_serde!(Class, u8, Elf32, Elf64,);
+
 impl Class {
     /// Word size in bytes.
     pub const fn word_len(self) -> usize {
@@ -25,19 +25,34 @@ pub const REL_LEN_64: usize = 16;
 pub const RELA_LEN_32: usize = 12;
 pub const RELA_LEN_64: usize = 24;
 
+pub const VERSYM_LEN: usize = 2;
+pub const SYMTAB_SHNDX_LEN: usize = 4;
+
 pub const SECTION_RESERVED_MIN: usize = 0xff00;
 pub const SECTION_RESERVED_MAX: usize = 0xffff;
+pub const SECTION_XINDEX: u16 = 0xffff;
 
 pub const DEFAULT_PAGE_SIZE: u64 = 4096;
+pub const DEFAULT_EXECUTABLE_BASE_ADDRESS: u64 = 0x400000;
+
+pub const READ_CHUNK_LEN: usize = 64 * 1024;
 
 pub const INTERP_SECTION: &CStr = c".interp";
 pub const SHSTRTAB_SECTION: &CStr = c".shstrtab";
 pub const DYNSTR_SECTION: &CStr = c".dynstr";
 pub const DYNAMIC_SECTION: &CStr = c".dynamic";
+pub const GOT_SECTION: &CStr = c".got";
+pub const GOT_PLT_SECTION: &CStr = c".got.plt";
+pub const RODATA_SECTION: &CStr = c".rodata";
+pub const SYMTAB_SECTION: &CStr = c".symtab";
+pub const STRTAB_SECTION: &CStr = c".strtab";
+pub const GNU_STACK_SECTION: &CStr = c".note.GNU-stack";
+pub const GNU_DEBUGLINK_SECTION: &CStr = c".gnu_debuglink";
 
 pub const INTERP_ALIGN: u64 = 1;
 pub const STRING_TABLE_ALIGN: u64 = 1;
 pub const DYNAMIC_ALIGN: u64 = 8;
-pub const DYNAMIC_ENTRY_LEN: u64 = 16;
 pub const PHDR_ALIGN: u64 = 8;
 pub const SECTION_HEADER_ALIGN: u64 = 8;
+pub const GNU_STACK_ALIGN: u64 = 16;
+pub const GNU_DEBUGLINK_ALIGN: u64 = 4;
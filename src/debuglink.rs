@@ -0,0 +1,188 @@
+use alloc::ffi::CString;
+use core::ffi::CStr;
+
+use crate::read_bytes_chunked;
+use crate::BlockRead;
+use crate::BlockWrite;
+use crate::ByteOrder;
+use crate::Class;
+use crate::ElfRead;
+use crate::ElfWrite;
+use crate::Error;
+
+/// The alignment of the CRC in `.gnu_debuglink` section.
+const DEBUGLINK_CRC_ALIGN: usize = 4;
+
+/// The contents of `.gnu_debuglink` section.
+///
+/// Links the stripped file to the separate debug information file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(test, derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DebugLink {
+    /// The file name of the debug information file.
+    pub file_name: CString,
+    /// CRC32 checksum of the debug information file.
+    ///
+    /// Use [`debuglink_crc32`] to compute the checksum.
+    pub crc: u32,
+}
+
+impl DebugLink {
+    /// Create new link to the file with the specified name and contents.
+    pub fn new(file_name: CString, contents: &[u8]) -> Self {
+        Self {
+            file_name,
+            crc: debuglink_crc32(0, contents),
+        }
+    }
+
+    /// Create new link to the specified debug information file.
+    ///
+    /// Only the file name is stored in the link,
+    /// the debugger searches for it in the debug directories.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        use std::io::Read;
+
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::Io(std::io::ErrorKind::InvalidInput.into()))?;
+        let file_name = CString::new(file_name.as_encoded_bytes()).map_err(|_| Error::CStr)?;
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = alloc::vec![0_u8; crate::constants::READ_CHUNK_LEN];
+        let mut crc = 0;
+        loop {
+            let n = file.read(&mut buf[..])?;
+            if n == 0 {
+                break;
+            }
+            crc = debuglink_crc32(crc, &buf[..n]);
+        }
+        Ok(Self { file_name, crc })
+    }
+}
+
+impl BlockRead for DebugLink {
+    fn read<R: ElfRead>(
+        reader: &mut R,
+        _class: Class,
+        byte_order: ByteOrder,
+        len: u64,
+    ) -> Result<Self, Error> {
+        let bytes = read_bytes_chunked(reader, len)?;
+        let file_name = CStr::from_bytes_until_nul(&bytes[..]).map_err(|_| Error::CStr)?;
+        let offset = name_len(file_name);
+        let crc = bytes
+            .get(offset..offset + 4)
+            .ok_or(Error::UnexpectedEof)?
+            .try_into()
+            .map_err(|_| Error::UnexpectedEof)?;
+        let crc = match byte_order {
+            ByteOrder::LittleEndian => u32::from_le_bytes(crc),
+            ByteOrder::BigEndian => u32::from_be_bytes(crc),
+        };
+        Ok(Self {
+            file_name: file_name.into(),
+            crc,
+        })
+    }
+}
+
+impl BlockWrite for DebugLink {
+    fn write<W: ElfWrite>(
+        &self,
+        writer: &mut W,
+        _class: Class,
+        byte_order: ByteOrder,
+    ) -> Result<(), Error> {
+        let bytes = self.file_name.to_bytes_with_nul();
+        writer.write_bytes(bytes)?;
+        let padding = name_len(&self.file_name) - bytes.len();
+        writer.write_bytes(&[0_u8; DEBUGLINK_CRC_ALIGN][..padding])?;
+        writer.write_u32(byte_order, self.crc)?;
+        Ok(())
+    }
+}
+
+/// The length of the file name including NUL byte and padding.
+fn name_len(file_name: &CStr) -> usize {
+    file_name
+        .count_bytes()
+        .saturating_add(1)
+        .next_multiple_of(DEBUGLINK_CRC_ALIGN)
+}
+
+/// Update CRC32 checksum `crc` with `bytes`.
+///
+/// This is the checksum that GDB uses to verify `.gnu_debuglink`. Start with zero `crc`.
+pub const fn debuglink_crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    let mut i = 0;
+    while i < bytes.len() {
+        crc = CRC32_TABLE[((crc ^ bytes[i] as u32) & 0xff) as usize] ^ (crc >> 8);
+        i += 1;
+    }
+    !crc
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::test::test_block_io;
+
+    #[test]
+    fn crc32() {
+        assert_eq!(0, debuglink_crc32(0, b""));
+        assert_eq!(0xcbf4_3926, debuglink_crc32(0, b"123456789"));
+        assert_eq!(
+            debuglink_crc32(0, b"123456789"),
+            debuglink_crc32(debuglink_crc32(0, b"1234"), b"56789")
+        );
+    }
+
+    #[test]
+    fn debuglink_layout() {
+        for (name, len) in [(c"", 8), (c"abc", 8), (c"abcd", 12)] {
+            let link = DebugLink {
+                file_name: name.into(),
+                crc: 0x1234_5678,
+            };
+            let mut buf = Vec::new();
+            link.write(&mut buf, Class::Elf64, ByteOrder::LittleEndian)
+                .unwrap();
+            assert_eq!(len, buf.len());
+            assert_eq!([0x78, 0x56, 0x34, 0x12], buf[len - 4..]);
+        }
+    }
+
+    #[test]
+    fn debuglink_io() {
+        test_block_io::<DebugLink>();
+    }
+}
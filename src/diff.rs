@@ -0,0 +1,624 @@
+use alloc::collections::BTreeSet;
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+use crate::DynamicTag;
+use crate::Elf;
+use crate::ElfRead;
+use crate::ElfSeek;
+use crate::Error;
+use crate::Header;
+use crate::Section;
+use crate::SectionKind;
+use crate::Segment;
+use crate::StringTable;
+use crate::SymbolTable;
+
+/// Structural summary of an ELF file.
+///
+/// Contains everything that is compared by [`ElfDiff`].
+#[derive(Debug, Clone)]
+pub struct ElfSummary {
+    /// File header.
+    pub header: Header,
+    /// Sections and their names.
+    pub sections: Vec<(CString, Section)>,
+    /// Segments.
+    pub segments: Vec<Segment>,
+    /// Interpreter.
+    pub interpreter: Option<CString>,
+    /// Dynamic table entries with the strings resolved.
+    pub dynamic: Vec<(DynamicTag, DynamicEntryValue)>,
+    /// The names of the symbols from `.symtab`.
+    pub symbols: BTreeSet<CString>,
+    /// The names of the symbols from `.dynsym`.
+    pub dynamic_symbols: BTreeSet<CString>,
+}
+
+impl ElfSummary {
+    /// Read the summary of `elf` from `file`.
+    pub fn read<R: ElfRead + ElfSeek>(elf: &Elf, file: &mut R) -> Result<Self, Error> {
+        let class = elf.header.class;
+        let byte_order = elf.header.byte_order;
+        let names = elf.read_section_names(file)?.unwrap_or_default();
+        let sections = elf
+            .sections
+            .iter()
+            .map(|section| {
+                let name = names
+                    .get_string(section.name_offset as usize)
+                    .unwrap_or_default();
+                (name.into(), section.clone())
+            })
+            .collect();
+        let interpreter = elf.read_interpreter(file)?;
+        let mut dynamic = Vec::new();
+        if let Some(table) = elf.read_dynamic_table(file)? {
+            let strings = elf.read_dynamic_string_table(file)?.unwrap_or_default();
+            for (tag, value) in table.into_inner().into_iter() {
+                let value = match tag {
+                    DynamicTag::Null => continue,
+                    DynamicTag::Needed
+                    | DynamicTag::Rpath
+                    | DynamicTag::Runpath
+                    | DynamicTag::SharedObjectName => match strings.get_string(value as usize) {
+                        Some(string) => DynamicEntryValue::CString(string.into()),
+                        None => DynamicEntryValue::Word(value),
+                    },
+                    _ => DynamicEntryValue::Word(value),
+                };
+                dynamic.push((tag, value));
+            }
+        }
+        let mut symbols = BTreeSet::new();
+        let mut dynamic_symbols = BTreeSet::new();
+        for section in elf.sections.iter() {
+            let names = match section.kind {
+                SectionKind::SymbolTable => &mut symbols,
+                SectionKind::DynamicSymbolTable => &mut dynamic_symbols,
+                _ => continue,
+            };
+            let Some(strings_section) = elf.sections.get(section.link as usize) else {
+                continue;
+            };
+            elf.limits().check_string_table_size(strings_section.size)?;
+            let table: SymbolTable = elf.read_section_content(section, file)?;
+            let strings: StringTable = strings_section.read_content(file, class, byte_order)?;
+            for symbol in table.iter() {
+                let Some(name) = strings.get_string(symbol.name_offset as usize) else {
+                    continue;
+                };
+                if name.is_empty() {
+                    continue;
+                }
+                names.insert(name.into());
+            }
+        }
+        Ok(Self {
+            header: elf.header.clone(),
+            sections,
+            segments: elf.segments.iter().cloned().collect(),
+            interpreter,
+            dynamic,
+            symbols,
+            dynamic_symbols,
+        })
+    }
+}
+
+/// Owned dynamic table entry's value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DynamicEntryValue {
+    /// C-string.
+    CString(CString),
+    /// Word.
+    Word(u64),
+}
+
+impl Display for DynamicEntryValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::CString(s) => write!(f, "{:?}", s),
+            Self::Word(w) => write!(f, "{:#x}", w),
+        }
+    }
+}
+
+/// Structural change.
+#[derive(Debug, Clone)]
+pub enum ElfChange {
+    /// File header field changed.
+    Header {
+        /// Field name.
+        field: &'static str,
+        /// Old value.
+        old: String,
+        /// New value.
+        new: String,
+    },
+    /// Section was added.
+    SectionAdded(CString, Section),
+    /// Section was removed.
+    SectionRemoved(CString, Section),
+    /// Section was moved, resized or its attributes changed.
+    SectionChanged {
+        /// Section name.
+        name: CString,
+        /// Old section.
+        old: Section,
+        /// New section.
+        new: Section,
+    },
+    /// Segment was added.
+    SegmentAdded(Segment),
+    /// Segment was removed.
+    SegmentRemoved(Segment),
+    /// Segment was moved, resized or its attributes changed.
+    SegmentChanged {
+        /// Old segment.
+        old: Segment,
+        /// New segment.
+        new: Segment,
+    },
+    /// Interpreter was added, removed or changed.
+    Interpreter {
+        /// Old interpreter.
+        old: Option<CString>,
+        /// New interpreter.
+        new: Option<CString>,
+    },
+    /// Dynamic table entry was added.
+    DynamicAdded(DynamicTag, DynamicEntryValue),
+    /// Dynamic table entry was removed.
+    DynamicRemoved(DynamicTag, DynamicEntryValue),
+    /// The value of the dynamic table entry changed.
+    DynamicChanged {
+        /// Entry tag.
+        tag: DynamicTag,
+        /// Old value.
+        old: DynamicEntryValue,
+        /// New value.
+        new: DynamicEntryValue,
+    },
+    /// Symbol was added.
+    SymbolAdded {
+        /// Symbol name.
+        name: CString,
+        /// Whether the symbol is in the dynamic symbol table.
+        dynamic: bool,
+    },
+    /// Symbol was removed.
+    SymbolRemoved {
+        /// Symbol name.
+        name: CString,
+        /// Whether the symbol is in the dynamic symbol table.
+        dynamic: bool,
+    },
+}
+
+impl Display for ElfChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Header { field, old, new } => write!(f, "~ header {field}: {old} -> {new}"),
+            Self::SectionAdded(name, section) => {
+                write!(f, "+ section {:?}: {}", name, SectionStr(section))
+            }
+            Self::SectionRemoved(name, section) => {
+                write!(f, "- section {:?}: {}", name, SectionStr(section))
+            }
+            Self::SectionChanged { name, old, new } => write!(
+                f,
+                "~ section {:?}: {} -> {}",
+                name,
+                SectionStr(old),
+                SectionStr(new)
+            ),
+            Self::SegmentAdded(segment) => write!(f, "+ segment {}", SegmentStr(segment)),
+            Self::SegmentRemoved(segment) => write!(f, "- segment {}", SegmentStr(segment)),
+            Self::SegmentChanged { old, new } => {
+                write!(f, "~ segment {} -> {}", SegmentStr(old), SegmentStr(new))
+            }
+            Self::Interpreter { old, new } => match (old, new) {
+                (None, Some(new)) => write!(f, "+ interpreter {:?}", new),
+                (Some(old), None) => write!(f, "- interpreter {:?}", old),
+                (Some(old), Some(new)) => write!(f, "~ interpreter {:?} -> {:?}", old, new),
+                (None, None) => Ok(()),
+            },
+            Self::DynamicAdded(tag, value) => write!(f, "+ dynamic {:?} {}", tag, value),
+            Self::DynamicRemoved(tag, value) => write!(f, "- dynamic {:?} {}", tag, value),
+            Self::DynamicChanged { tag, old, new } => {
+                write!(f, "~ dynamic {:?} {} -> {}", tag, old, new)
+            }
+            Self::SymbolAdded { name, dynamic } => {
+                write!(f, "+ {} {:?}", symbol_kind(*dynamic), name)
+            }
+            Self::SymbolRemoved { name, dynamic } => {
+                write!(f, "- {} {:?}", symbol_kind(*dynamic), name)
+            }
+        }
+    }
+}
+
+/// Structural difference between two ELF files.
+///
+/// Sections are matched by name, segments are matched by type and their order,
+/// dynamic table entries are matched by tag.
+#[derive(Debug, Clone, Default)]
+pub struct ElfDiff {
+    changes: Vec<ElfChange>,
+}
+
+impl ElfDiff {
+    /// Compute the difference between `old` and `new` files.
+    pub fn new(old: &ElfSummary, new: &ElfSummary) -> Self {
+        let mut changes = Vec::new();
+        diff_header(&old.header, &new.header, &mut changes);
+        diff_sections(&old.sections, &new.sections, &mut changes);
+        diff_segments(&old.segments, &new.segments, &mut changes);
+        if old.interpreter != new.interpreter {
+            changes.push(ElfChange::Interpreter {
+                old: old.interpreter.clone(),
+                new: new.interpreter.clone(),
+            });
+        }
+        diff_dynamic(&old.dynamic, &new.dynamic, &mut changes);
+        diff_symbols(&old.symbols, &new.symbols, false, &mut changes);
+        diff_symbols(
+            &old.dynamic_symbols,
+            &new.dynamic_symbols,
+            true,
+            &mut changes,
+        );
+        Self { changes }
+    }
+
+    /// Get the list of changes.
+    pub fn changes(&self) -> &[ElfChange] {
+        &self.changes
+    }
+
+    /// Returns `true` if the files are structurally the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for ElfDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+fn diff_header(old: &Header, new: &Header, changes: &mut Vec<ElfChange>) {
+    macro_rules! diff_field {
+        ($field: ident, $format: literal) => {
+            if old.$field != new.$field {
+                changes.push(ElfChange::Header {
+                    field: stringify!($field),
+                    old: format!($format, old.$field),
+                    new: format!($format, new.$field),
+                });
+            }
+        };
+    }
+    diff_field!(class, "{:?}");
+    diff_field!(byte_order, "{:?}");
+    diff_field!(os_abi, "{:?}");
+    diff_field!(abi_version, "{}");
+    diff_field!(kind, "{:?}");
+    diff_field!(machine, "{:?}");
+    diff_field!(flags, "{:#x}");
+    diff_field!(entry_point, "{:#x}");
+    diff_field!(program_header_offset, "{:#x}");
+    diff_field!(section_header_offset, "{:#x}");
+}
+
+fn diff_sections(
+    old: &[(CString, Section)],
+    new: &[(CString, Section)],
+    changes: &mut Vec<ElfChange>,
+) {
+    // Sections with the same name are matched in the order of appearance.
+    let mut matched = Vec::new();
+    matched.resize(new.len(), false);
+    for (old_name, old_section) in old.iter() {
+        let Some(i) = new
+            .iter()
+            .enumerate()
+            .position(|(i, (new_name, _))| !matched[i] && new_name == old_name)
+        else {
+            changes.push(ElfChange::SectionRemoved(
+                old_name.clone(),
+                old_section.clone(),
+            ));
+            continue;
+        };
+        matched[i] = true;
+        let new_section = &new[i].1;
+        if !same_section(old_section, new_section) {
+            changes.push(ElfChange::SectionChanged {
+                name: old_name.clone(),
+                old: old_section.clone(),
+                new: new_section.clone(),
+            });
+        }
+    }
+    for ((name, section), matched) in new.iter().zip(matched) {
+        if !matched {
+            changes.push(ElfChange::SectionAdded(name.clone(), section.clone()));
+        }
+    }
+}
+
+fn diff_segments(old: &[Segment], new: &[Segment], changes: &mut Vec<ElfChange>) {
+    // Segments of the same type are matched in the order of appearance.
+    let mut matched = Vec::new();
+    matched.resize(new.len(), false);
+    for old_segment in old.iter() {
+        let Some(i) = new
+            .iter()
+            .enumerate()
+            .position(|(i, new_segment)| !matched[i] && new_segment.kind == old_segment.kind)
+        else {
+            changes.push(ElfChange::SegmentRemoved(old_segment.clone()));
+            continue;
+        };
+        matched[i] = true;
+        let new_segment = &new[i];
+        if !same_segment(old_segment, new_segment) {
+            changes.push(ElfChange::SegmentChanged {
+                old: old_segment.clone(),
+                new: new_segment.clone(),
+            });
+        }
+    }
+    for (segment, matched) in new.iter().zip(matched) {
+        if !matched {
+            changes.push(ElfChange::SegmentAdded(segment.clone()));
+        }
+    }
+}
+
+fn diff_dynamic(
+    old: &[(DynamicTag, DynamicEntryValue)],
+    new: &[(DynamicTag, DynamicEntryValue)],
+    changes: &mut Vec<ElfChange>,
+) {
+    let mut tags: Vec<DynamicTag> = Vec::new();
+    for (tag, _) in old.iter().chain(new.iter()) {
+        if !tags.contains(tag) {
+            tags.push(*tag);
+        }
+    }
+    for tag in tags.into_iter() {
+        let values = |entries: &[(DynamicTag, DynamicEntryValue)]| -> Vec<DynamicEntryValue> {
+            entries
+                .iter()
+                .filter(|(t, _)| *t == tag)
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+        let old_values = values(old);
+        let mut new_values = values(new);
+        if let ([old_value], [new_value]) = (&old_values[..], &new_values[..]) {
+            if old_value != new_value {
+                changes.push(ElfChange::DynamicChanged {
+                    tag,
+                    old: old_value.clone(),
+                    new: new_value.clone(),
+                });
+            }
+            continue;
+        }
+        // Multi-valued tags (e.g. NEEDED) are compared as multisets.
+        for old_value in old_values.into_iter() {
+            match new_values.iter().position(|value| *value == old_value) {
+                Some(i) => {
+                    new_values.remove(i);
+                }
+                None => changes.push(ElfChange::DynamicRemoved(tag, old_value)),
+            }
+        }
+        for new_value in new_values.into_iter() {
+            changes.push(ElfChange::DynamicAdded(tag, new_value));
+        }
+    }
+}
+
+fn diff_symbols(
+    old: &BTreeSet<CString>,
+    new: &BTreeSet<CString>,
+    dynamic: bool,
+    changes: &mut Vec<ElfChange>,
+) {
+    for name in old.difference(new) {
+        changes.push(ElfChange::SymbolRemoved {
+            name: name.clone(),
+            dynamic,
+        });
+    }
+    for name in new.difference(old) {
+        changes.push(ElfChange::SymbolAdded {
+            name: name.clone(),
+            dynamic,
+        });
+    }
+}
+
+// Section name offset is ignored because the names are compared directly.
+fn same_section(a: &Section, b: &Section) -> bool {
+    a.kind == b.kind
+        && a.flags == b.flags
+        && a.virtual_address == b.virtual_address
+        && a.offset == b.offset
+        && a.size == b.size
+        && a.link == b.link
+        && a.info == b.info
+        && a.align == b.align
+        && a.entry_len == b.entry_len
+}
+
+fn same_segment(a: &Segment, b: &Segment) -> bool {
+    a.kind == b.kind
+        && a.flags == b.flags
+        && a.offset == b.offset
+        && a.virtual_address == b.virtual_address
+        && a.physical_address == b.physical_address
+        && a.file_size == b.file_size
+        && a.memory_size == b.memory_size
+        && a.align == b.align
+}
+
+const fn symbol_kind(dynamic: bool) -> &'static str {
+    if dynamic {
+        "dynamic symbol"
+    } else {
+        "symbol"
+    }
+}
+
+struct SectionStr<'a>(&'a Section);
+
+impl Display for SectionStr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let s = self.0;
+        write!(
+            f,
+            "{:?} {:?} file {:#x}..{:#x} memory {:#x}..{:#x}",
+            s.kind,
+            s.flags,
+            s.offset,
+            s.offset.saturating_add(s.size),
+            s.virtual_address,
+            s.virtual_address.saturating_add(s.size),
+        )
+    }
+}
+
+struct SegmentStr<'a>(&'a Segment);
+
+impl Display for SegmentStr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let s = self.0;
+        write!(
+            f,
+            "{:?} {:?} file {:#x}..{:#x} memory {:#x}..{:#x}",
+            s.kind,
+            s.flags,
+            s.offset,
+            s.offset.saturating_add(s.file_size),
+            s.virtual_address,
+            s.virtual_address.saturating_add(s.memory_size),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::Cursor;
+    use crate::ElfPatcher;
+    use crate::SegmentFlags;
+    use crate::SegmentKind;
+
+    #[test]
+    fn diff_patched_file() {
+        let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+        let mut file = Cursor::new(bytes.clone());
+        let elf = Elf::read(&mut file, DEFAULT_PAGE_SIZE).unwrap();
+        let old = ElfSummary::read(&elf, &mut file).unwrap();
+        assert!(ElfDiff::new(&old, &old).is_empty());
+        let Some(old_interpreter) = old.interpreter.clone() else {
+            return;
+        };
+        let mut patcher = ElfPatcher::new(elf, file);
+        patcher
+            .set_interpreter(c"/a/very/long/path/to/the/dynamic/loader.so")
+            .unwrap();
+        let mut file = patcher.finish().unwrap();
+        let elf = Elf::read(&mut file, DEFAULT_PAGE_SIZE).unwrap();
+        let new = ElfSummary::read(&elf, &mut file).unwrap();
+        let diff = ElfDiff::new(&old, &new);
+        assert!(diff.changes().iter().any(|change| matches!(
+            change,
+            ElfChange::Interpreter { old: Some(old), new: Some(new) }
+                if *old == old_interpreter
+                    && new.as_c_str() == c"/a/very/long/path/to/the/dynamic/loader.so"
+        )));
+        assert!(diff.changes().iter().any(|change| matches!(
+            change,
+            ElfChange::SectionChanged { name, .. } if name.as_c_str() == INTERP_SECTION
+        )));
+        assert!(diff.changes().iter().any(|change| matches!(
+            change,
+            ElfChange::Header {
+                field: "program_header_offset",
+                ..
+            }
+        )));
+        // Nothing but the layout changed.
+        assert!(!diff.changes().iter().any(|change| matches!(
+            change,
+            ElfChange::SymbolAdded { .. } | ElfChange::SymbolRemoved { .. }
+        )));
+        let reverse = ElfDiff::new(&new, &old);
+        assert_eq!(diff.changes().len(), reverse.changes().len());
+    }
+
+    #[test]
+    fn multi_valued_dynamic_entries() {
+        let needed =
+            |name: &core::ffi::CStr| (DynamicTag::Needed, DynamicEntryValue::CString(name.into()));
+        let mut changes = Vec::new();
+        diff_dynamic(
+            &[needed(c"libc.so.6"), needed(c"libm.so.6")],
+            &[needed(c"libm.so.6"), needed(c"libz.so.1")],
+            &mut changes,
+        );
+        assert_eq!(2, changes.len());
+        assert!(matches!(
+            &changes[0],
+            ElfChange::DynamicRemoved(DynamicTag::Needed, DynamicEntryValue::CString(s))
+                if s.as_c_str() == c"libc.so.6"
+        ));
+        assert!(matches!(
+            &changes[1],
+            ElfChange::DynamicAdded(DynamicTag::Needed, DynamicEntryValue::CString(s))
+                if s.as_c_str() == c"libz.so.1"
+        ));
+    }
+
+    #[test]
+    fn display_out_of_range_sections_and_segments() {
+        let section = Section {
+            offset: u64::MAX,
+            virtual_address: u64::MAX,
+            size: u64::MAX,
+            ..Default::default()
+        };
+        let segment = Segment {
+            kind: SegmentKind::Loadable,
+            flags: SegmentFlags::READABLE,
+            offset: u64::MAX,
+            virtual_address: u64::MAX,
+            physical_address: u64::MAX,
+            file_size: u64::MAX,
+            memory_size: u64::MAX,
+            align: 1,
+        };
+        assert!(
+            format!("{}", SectionStr(&section)).contains("0xffffffffffffffff..0xffffffffffffffff")
+        );
+        assert!(
+            format!("{}", SegmentStr(&segment)).contains("0xffffffffffffffff..0xffffffffffffffff")
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::ops::Range;
+
+use crate::Elf;
+use crate::ElfDiff;
+use crate::ElfRead;
+use crate::ElfSeek;
+use crate::ElfSummary;
+use crate::ElfTruncate;
+use crate::ElfWrite;
+use crate::Error;
+use crate::Header;
+
+/// File wrapper that records the writes instead of applying them.
+///
+/// Pass it to [`ElfPatcher`](crate::ElfPatcher) to see what the patcher is going to change
+/// without modifying the file. The reads see the recorded writes, i.e. the patcher works as
+/// usual. Call [`plan`](Self::plan) after [`finish`](crate::ElfPatcher::finish) to get the
+/// summary of the changes.
+pub struct DryRun<F> {
+    file: F,
+    position: u64,
+    /// Original file length.
+    file_len: Option<u64>,
+    /// Current file length.
+    len: Option<u64>,
+    /// Non-overlapping written blocks indexed by their offset.
+    writes: BTreeMap<u64, Vec<u8>>,
+}
+
+impl<F> DryRun<F> {
+    /// Create new wrapper for the `file`.
+    pub const fn new(file: F) -> Self {
+        Self {
+            file,
+            position: 0,
+            file_len: None,
+            len: None,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    /// Get the underlying file.
+    ///
+    /// The file is never modified.
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+impl<F: ElfRead + ElfSeek> DryRun<F> {
+    /// Summarize the recorded changes.
+    ///
+    /// Compares the original file with the file that the patcher would have produced.
+    pub fn plan(&mut self, page_size: u64) -> Result<PatchPlan, Error> {
+        let old_len = self.file.file_len()?.unwrap_or(u64::MAX);
+        let old_elf = Elf::read_unchecked(&mut self.file, page_size)?;
+        let old_summary = ElfSummary::read(&old_elf, &mut self.file)?;
+        let new_elf = Elf::read_unchecked(self, page_size)?;
+        let new_summary = ElfSummary::read(&new_elf, self)?;
+        let mut writes = Vec::with_capacity(self.writes.len());
+        let mut old_bytes = Vec::new();
+        for (offset, bytes) in self.writes.iter() {
+            // Compare with the original contents.
+            let offset = *offset;
+            let end = offset + bytes.len() as u64;
+            let old_end = end.min(old_len).max(offset);
+            old_bytes.clear();
+            old_bytes.resize((old_end - offset) as usize, 0_u8);
+            if !old_bytes.is_empty() {
+                self.file.seek(offset)?;
+                self.file.read_bytes(&mut old_bytes[..])?;
+            }
+            let num_same_prefix = bytes
+                .iter()
+                .zip(old_bytes.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            if num_same_prefix == bytes.len() {
+                // Nothing changed.
+                continue;
+            }
+            let num_same_suffix = if bytes.len() == old_bytes.len() {
+                bytes
+                    .iter()
+                    .rev()
+                    .zip(old_bytes.iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            } else {
+                0
+            };
+            let changed = &bytes[num_same_prefix..bytes.len() - num_same_suffix];
+            let start = offset + num_same_prefix as u64;
+            writes.push(PlannedWrite {
+                range: start..start + changed.len() as u64,
+                zero: changed.iter().all(|b| *b == 0),
+            });
+        }
+        Ok(PatchPlan {
+            writes,
+            old_len,
+            new_len: self.current_len()?,
+            header: new_elf.header.clone(),
+            diff: ElfDiff::new(&old_summary, &new_summary),
+        })
+    }
+
+    /// Original file length.
+    fn original_len(&mut self) -> Result<u64, Error> {
+        match self.file_len {
+            Some(len) => Ok(len),
+            None => {
+                let len = self.file.file_len()?.unwrap_or(u64::MAX);
+                self.file_len = Some(len);
+                Ok(len)
+            }
+        }
+    }
+
+    /// Current file length.
+    fn current_len(&mut self) -> Result<u64, Error> {
+        match self.len {
+            Some(len) => Ok(len),
+            None => self.original_len(),
+        }
+    }
+}
+
+impl<F: ElfRead + ElfSeek> ElfRead for DryRun<F> {
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let start = self.position;
+        let end = start
+            .checked_add(buf.len() as u64)
+            .ok_or(Error::TooBig("Read offset"))?;
+        if end > self.current_len()? {
+            return Err(Error::UnexpectedEof);
+        }
+        // Read the original contents.
+        let file_end = end.min(self.original_len()?).max(start);
+        let n = (file_end - start) as usize;
+        buf[n..].fill(0);
+        if n != 0 {
+            self.file.seek(start)?;
+            self.file.read_bytes(&mut buf[..n])?;
+        }
+        // Apply the writes.
+        for (offset, bytes) in self.writes.range(..end) {
+            let block_end = offset + bytes.len() as u64;
+            if block_end <= start {
+                continue;
+            }
+            let from = start.max(*offset);
+            let to = end.min(block_end);
+            buf[(from - start) as usize..(to - start) as usize]
+                .copy_from_slice(&bytes[(from - offset) as usize..(to - offset) as usize]);
+        }
+        self.position = end;
+        Ok(())
+    }
+}
+
+impl<F: ElfRead + ElfSeek> ElfWrite for DryRun<F> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let mut start = self.position;
+        let mut end = start
+            .checked_add(bytes.len() as u64)
+            .ok_or(Error::TooBig("Write offset"))?;
+        self.position = end;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        // Merge with overlapping and adjacent blocks.
+        let merged: Vec<u64> = self
+            .writes
+            .range(..=end)
+            .rev()
+            .take_while(|(offset, block)| **offset + block.len() as u64 >= start)
+            .map(|(offset, _)| *offset)
+            .collect();
+        let old_blocks: Vec<(u64, Vec<u8>)> = merged
+            .into_iter()
+            .filter_map(|offset| self.writes.remove_entry(&offset))
+            .collect();
+        for (offset, block) in old_blocks.iter() {
+            start = start.min(*offset);
+            end = end.max(offset + block.len() as u64);
+        }
+        let mut new_block = vec![0_u8; (end - start) as usize];
+        for (offset, block) in old_blocks.iter() {
+            let i = (offset - start) as usize;
+            new_block[i..i + block.len()].copy_from_slice(block);
+        }
+        let i = (self.position - bytes.len() as u64 - start) as usize;
+        new_block[i..i + bytes.len()].copy_from_slice(bytes);
+        self.writes.insert(start, new_block);
+        if self.position > self.current_len()? {
+            self.len = Some(self.position);
+        }
+        Ok(())
+    }
+}
+
+impl<F: ElfRead + ElfSeek> ElfSeek for DryRun<F> {
+    fn seek(&mut self, offset: u64) -> Result<(), Error> {
+        self.position = offset;
+        Ok(())
+    }
+
+    fn file_len(&mut self) -> Result<Option<u64>, Error> {
+        Ok(Some(self.current_len()?))
+    }
+}
+
+impl<F: ElfRead + ElfSeek> ElfTruncate for DryRun<F> {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        let old_len = self.current_len()?;
+        if len < old_len {
+            // Drop the writes past the end of the file.
+            self.writes.retain(|offset, _| *offset < len);
+            if let Some((offset, block)) = self.writes.iter_mut().next_back() {
+                block.truncate(len.saturating_sub(*offset) as usize);
+            }
+            // The original contents past the new end are discarded.
+            let file_len = self.original_len()?.min(len);
+            self.file_len = Some(file_len);
+        }
+        self.len = Some(len);
+        Ok(())
+    }
+}
+
+/// Summary of the changes that [`ElfPatcher`](crate::ElfPatcher) is going to make.
+///
+/// Produced by [`DryRun::plan`].
+#[derive(Debug, Clone)]
+pub struct PatchPlan {
+    /// Changed byte ranges sorted by offset.
+    pub writes: Vec<PlannedWrite>,
+    /// Original file length.
+    pub old_len: u64,
+    /// New file length.
+    pub new_len: u64,
+    /// New file header.
+    pub header: Header,
+    /// Added, removed and changed sections, segments, header fields and dynamic table entries.
+    pub diff: ElfDiff,
+}
+
+impl Display for PatchPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for write in self.writes.iter() {
+            writeln!(f, "{}", write)?;
+        }
+        if self.old_len != self.new_len {
+            writeln!(f, "~ file length: {} -> {}", self.old_len, self.new_len)?;
+        }
+        write!(f, "{}", self.diff)
+    }
+}
+
+/// Changed byte range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    /// In-file offsets.
+    pub range: Range<u64>,
+    /// The range is filled with zeroes.
+    pub zero: bool,
+}
+
+impl Display for PlannedWrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let verb = if self.zero { "zero" } else { "write" };
+        write!(
+            f,
+            "{} {:#x}..{:#x} ({} bytes)",
+            verb,
+            self.range.start,
+            self.range.end,
+            self.range.end - self.range.start
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Cursor;
+
+    #[test]
+    fn read_write() {
+        let original: Vec<u8> = (0..16).collect();
+        let mut file = DryRun::new(Cursor::new(original.clone()));
+        file.seek(4).unwrap();
+        file.write_bytes(&[0xff; 4]).unwrap();
+        file.seek(6).unwrap();
+        file.write_bytes(&[0xee; 4]).unwrap();
+        file.seek(14).unwrap();
+        file.write_bytes(&[0xdd; 4]).unwrap();
+        let mut expected = original.clone();
+        expected[4..6].fill(0xff);
+        expected[6..10].fill(0xee);
+        expected.resize(18, 0xdd);
+        expected[14..].fill(0xdd);
+        let mut actual = vec![0_u8; expected.len()];
+        file.seek(0).unwrap();
+        file.read_bytes(&mut actual[..]).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(Some(18), file.file_len().unwrap());
+        assert_eq!(2, file.writes.len());
+        assert!(file.read_bytes(&mut [0_u8; 1]).is_err());
+        file.truncate(8).unwrap();
+        file.truncate(12).unwrap();
+        expected.truncate(8);
+        expected.resize(12, 0);
+        let mut actual = vec![0_u8; 12];
+        file.seek(0).unwrap();
+        file.read_bytes(&mut actual[..]).unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(original, file.into_inner().into_inner());
+    }
+}
@@ -3,6 +3,7 @@ use core::ffi::CStr;
 use core::ops::Deref;
 use core::ops::DerefMut;
 
+use crate::constants::*;
 use crate::io::*;
 use crate::BlockRead;
 use crate::BlockWrite;
@@ -13,6 +14,8 @@ use crate::Error;
 
 /// Dynamic linking information.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct DynamicTable {
     entries: Vec<(DynamicTag, u64)>,
 }
@@ -46,8 +49,10 @@ impl BlockRead for DynamicTable {
         byte_order: ByteOrder,
         len: u64,
     ) -> Result<Self, Error> {
-        let mut entries = Vec::with_capacity((len / class.dynamic_len() as u64) as usize);
         let step = class.dynamic_len();
+        // The length comes from the file and can't be trusted, hence the upper bound.
+        let num_entries = (len / step as u64).min((READ_CHUNK_LEN / step) as u64);
+        let mut entries = Vec::with_capacity(num_entries as usize);
         for _ in (0..len).step_by(step) {
             let tag: DynamicTag = reader.read_word(class, byte_order)?.try_into()?;
             if tag == DynamicTag::Null {
@@ -2,16 +2,23 @@ use alloc::ffi::CString;
 use alloc::vec::Vec;
 use core::ffi::CStr;
 
+use crate::check_file_bounds;
 use crate::constants::*;
 use crate::BlockRead;
 use crate::BlockWrite;
+use crate::DebugLink;
 use crate::DynamicTable;
+use crate::DynamicTag;
 use crate::ElfRead;
+use crate::ElfReadAt;
 use crate::ElfSeek;
 use crate::ElfWrite;
 use crate::Error;
 use crate::Header;
+use crate::Limits;
 use crate::ProgramHeader;
+use crate::ReadAtCursor;
+use crate::Section;
 use crate::SectionHeader;
 use crate::SectionKind;
 use crate::SegmentKind;
@@ -27,16 +34,66 @@ pub struct Elf {
     /// Section header (file section list).
     pub sections: SectionHeader,
     page_size: u64,
+    limits: Limits,
 }
 
 impl Elf {
+    pub(crate) fn new(
+        header: Header,
+        segments: ProgramHeader,
+        sections: SectionHeader,
+        page_size: u64,
+    ) -> Self {
+        Self {
+            header,
+            segments,
+            sections,
+            page_size,
+            limits: Limits::default(),
+        }
+    }
+
     /// Read ELF from `reader` without validation.
     pub fn read_unchecked<R: ElfRead + ElfSeek>(
         reader: &mut R,
         page_size: u64,
     ) -> Result<Self, Error> {
+        Self::read_unchecked_with_limits(reader, page_size, Limits::default())
+    }
+
+    /// Read ELF from `reader` with validation.
+    ///
+    /// Page size is used during the validation.
+    pub fn read<R: ElfRead + ElfSeek>(reader: &mut R, page_size: u64) -> Result<Self, Error> {
+        Self::read_with_limits(reader, page_size, Limits::default())
+    }
+
+    /// Read ELF from `reader` without validation, but with the specified limits.
+    ///
+    /// The limits are checked before allocating the memory. They are also checked when reading
+    /// section contents via `Elf::read_*` methods.
+    pub fn read_unchecked_with_limits<R: ElfRead + ElfSeek>(
+        reader: &mut R,
+        page_size: u64,
+        limits: Limits,
+    ) -> Result<Self, Error> {
+        let file_len = reader.file_len()?;
         reader.seek(0)?;
         let header = Header::read(reader)?;
+        limits.check_num_segments(header.num_segments as u64)?;
+        limits.check_num_sections(header.num_sections as u64)?;
+        if let Some(file_len) = file_len {
+            check_file_bounds(
+                header.program_header_offset,
+                header.program_header_len(),
+                file_len,
+            )?;
+            check_file_bounds(
+                header.section_header_offset,
+                header.section_header_len(),
+                file_len,
+            )?;
+        }
         reader.seek(header.program_header_offset)?;
         let segments = ProgramHeader::read(
             reader,
@@ -51,23 +108,58 @@ impl Elf {
             header.byte_order,
             header.section_header_len(),
         )?;
+        if let Some(file_len) = file_len {
+            for segment in segments.iter() {
+                check_file_bounds(segment.offset, segment.file_size, file_len)?;
+            }
+            for section in sections.iter() {
+                if section.kind == SectionKind::NoBits {
+                    continue;
+                }
+                check_file_bounds(section.offset, section.size, file_len)?;
+            }
+        }
         Ok(Self {
             header,
             segments,
             sections,
             page_size,
+            limits,
         })
     }
 
-    /// Read ELF from `reader` with validation.
+    /// Read ELF from `reader` with validation and with the specified limits.
     ///
     /// Page size is used during the validation.
-    pub fn read<R: ElfRead + ElfSeek>(reader: &mut R, page_size: u64) -> Result<Self, Error> {
-        let elf = Self::read_unchecked(reader, page_size)?;
+    pub fn read_with_limits<R: ElfRead + ElfSeek>(
+        reader: &mut R,
+        page_size: u64,
+        limits: Limits,
+    ) -> Result<Self, Error> {
+        let elf = Self::read_unchecked_with_limits(reader, page_size, limits)?;
         elf.check()?;
         Ok(elf)
     }
 
+    /// Read ELF from `reader` using positional reads without validation.
+    ///
+    /// Doesn't require mutable access to `reader`, i.e. the same file can be shared between
+    /// multiple threads.
+    pub fn read_unchecked_at<R: ElfReadAt + ?Sized>(
+        reader: &R,
+        page_size: u64,
+    ) -> Result<Self, Error> {
+        Self::read_unchecked(&mut ReadAtCursor::new(reader), page_size)
+    }
+
+    /// Read ELF from `reader` using positional reads with validation.
+    ///
+    /// Doesn't require mutable access to `reader`, i.e. the same file can be shared between
+    /// multiple threads.
+    pub fn read_at<R: ElfReadAt + ?Sized>(reader: &R, page_size: u64) -> Result<Self, Error> {
+        Self::read(&mut ReadAtCursor::new(reader), page_size)
+    }
+
     /// Validate and write ELF to `writer`.
     pub fn write<W: ElfWrite + ElfSeek>(self, writer: &mut W) -> Result<(), Error> {
         self.check()?;
@@ -107,6 +199,7 @@ impl Elf {
         let Some(section) = self.sections.get(self.header.section_names_index as usize) else {
             return Ok(None);
         };
+        self.limits.check_string_table_size(section.size)?;
         Ok(Some(section.read_content(
             file,
             self.header.class,
@@ -115,47 +208,84 @@ impl Elf {
     }
 
     /// Read dynamic table.
+    ///
+    /// Falls back to `DYNAMIC` segment if there is no `.dynamic` section, e.g. if the file has
+    /// no section header.
     pub fn read_dynamic_table<F: ElfRead + ElfSeek>(
         &self,
         file: &mut F,
     ) -> Result<Option<DynamicTable>, Error> {
-        let Some(i) = self
+        let (offset, size) = match self
             .sections
             .iter()
-            .position(|section| section.kind == SectionKind::Dynamic)
-        else {
-            return Ok(None);
+            .find(|section| section.kind == SectionKind::Dynamic)
+        {
+            Some(section) => (section.offset, section.size),
+            None => match self
+                .segments
+                .iter()
+                .find(|segment| segment.kind == SegmentKind::Dynamic)
+            {
+                Some(segment) => (segment.offset, segment.file_size),
+                None => return Ok(None),
+            },
         };
-        let section = &self.sections[i];
-        file.seek(section.offset)?;
-        let table = DynamicTable::read(
-            file,
-            self.header.class,
-            self.header.byte_order,
-            section.size,
-        )?;
+        self.limits.check_section_size(size)?;
+        file.seek(offset)?;
+        let table = DynamicTable::read(file, self.header.class, self.header.byte_order, size)?;
         Ok(Some(table))
     }
 
     /// Read dynamic string table.
+    ///
+    /// Falls back to `STRTAB` and `STRSZ` dynamic table entries if there is no `.dynstr`
+    /// section, e.g. if the file has no section header.
     pub fn read_dynamic_string_table<F: ElfRead + ElfSeek>(
         &self,
         file: &mut F,
     ) -> Result<Option<StringTable>, Error> {
-        let Some(names) = self.read_section_names(file)? else {
+        if let Some(names) = self.read_section_names(file)? {
+            if let Some(section) = self.sections.iter().find(|section| {
+                Some(DYNSTR_SECTION) == names.get_string(section.name_offset as usize)
+            }) {
+                self.limits.check_string_table_size(section.size)?;
+                let table =
+                    section.read_content(file, self.header.class, self.header.byte_order)?;
+                return Ok(Some(table));
+            }
+        }
+        let Some(dynamic_table) = self.read_dynamic_table(file)? else {
             return Ok(None);
         };
-        let table = match self.sections.iter().position(|section| {
-            Some(DYNSTR_SECTION) == names.get_string(section.name_offset as usize)
-        }) {
-            Some(i) => {
-                self.sections[i].read_content(file, self.header.class, self.header.byte_order)?
-            }
-            None => return Ok(None),
+        let (Some(address), Some(size)) = (
+            dynamic_table.get(DynamicTag::StringTableAddress),
+            dynamic_table.get(DynamicTag::StringTableSize),
+        ) else {
+            return Ok(None);
         };
+        let Some(offset) = self.address_to_offset(address, size) else {
+            return Ok(None);
+        };
+        self.limits.check_string_table_size(size)?;
+        file.seek(offset)?;
+        let table = StringTable::read(file, size)?;
         Ok(Some(table))
     }
 
+    /// Convert virtual address range to in-file offset using `LOAD` segments.
+    ///
+    /// Returns `None` if the range is not mapped from the file.
+    pub(crate) fn address_to_offset(&self, address: u64, len: u64) -> Option<u64> {
+        let end = address.checked_add(len)?;
+        self.segments.iter().find_map(|segment| {
+            let start = segment.virtual_address;
+            (segment.kind == SegmentKind::Loadable
+                && start <= address
+                && end <= start.saturating_add(segment.file_size))
+            .then(|| segment.offset + (address - start))
+        })
+    }
+
     /// Read the interpreter.
     pub fn read_interpreter<F: ElfRead + ElfSeek>(
         &self,
@@ -168,8 +298,9 @@ impl Elf {
         else {
             return Ok(None);
         };
-        let interp =
-            self.segments[i].read_content(file, self.header.class, self.header.byte_order)?;
+        let segment = &self.segments[i];
+        self.limits.check_section_size(segment.file_size)?;
+        let interp = segment.read_content(file, self.header.class, self.header.byte_order)?;
         Ok(Some(CString::from_vec_with_nul(interp)?))
     }
 
@@ -187,15 +318,42 @@ impl Elf {
         else {
             return Ok(None);
         };
-        Ok(Some(self.sections[i].read_content(
-            file,
-            self.header.class,
-            self.header.byte_order,
-        )?))
+        Ok(Some(self.read_section_content(&self.sections[i], file)?))
+    }
+
+    /// Read the contents of the section checking [`max_section_size`](Limits::max_section_size).
+    pub fn read_section_content<R: ElfRead + ElfSeek, T: BlockRead>(
+        &self,
+        section: &Section,
+        file: &mut R,
+    ) -> Result<T, Error> {
+        self.limits.check_section_size(section.size)?;
+        section.read_content(file, self.header.class, self.header.byte_order)
+    }
+
+    /// Read the link to the debug information file from `.gnu_debuglink` section.
+    pub fn read_debuglink<R: ElfRead + ElfSeek>(
+        &self,
+        file: &mut R,
+    ) -> Result<Option<DebugLink>, Error> {
+        let Some(names) = self.read_section_names(file)? else {
+            return Ok(None);
+        };
+        let Some(section) = self.sections.iter().find(|section| {
+            Some(GNU_DEBUGLINK_SECTION) == names.get_string(section.name_offset as usize)
+        }) else {
+            return Ok(None);
+        };
+        Ok(Some(self.read_section_content(section, file)?))
     }
 
     /// Get page size specified on creation.
     pub fn page_size(&self) -> u64 {
         self.page_size
     }
+
+    /// Get limits specified on creation.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
 }
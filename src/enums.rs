@@ -1,5 +1,6 @@
 #![allow(missing_docs)]
 
+use crate::define_enum_serde;
 use crate::define_enum_v2;
 use crate::define_infallible_enum;
 use crate::Error;
@@ -238,6 +239,22 @@ impl Machine {
     pub const fn as_u16(self) -> u16 {
         self.as_number()
     }
+
+    /// Relocation type that adds the load address to the addend (`R_*_RELATIVE`).
+    ///
+    /// Returns `None` if the type is not known for this machine.
+    pub const fn relative_relocation_kind(self) -> Option<u32> {
+        match self {
+            Self::I386 | Self::X86_64 => Some(8),
+            Self::Aarch64 => Some(1027),
+            Self::Arm => Some(23),
+            Self::Ppc | Self::Ppc64 => Some(22),
+            Self::S390 => Some(12),
+            Self::Sparc | Self::Sparcv9 => Some(22),
+            Self::Riscv | Self::Loongarch => Some(3),
+            _ => None,
+        }
+    }
 }
 
 define_infallible_enum! {
@@ -253,7 +270,19 @@ define_infallible_enum! {
     (Tls, 7, "A segment that contains thread-local storage."),
 }
 
+// GNU extensions are constants rather than variants, so that they keep decoding as `Other`.
 impl SegmentKind {
+    /// A segment that contains exception handling frame header (`PT_GNU_EH_FRAME`).
+    pub const GNU_EH_FRAME: Self = Self::Other(0x6474e550);
+    /// A segment which flags specify stack permissions (`PT_GNU_STACK`).
+    pub const GNU_STACK: Self = Self::Other(0x6474e551);
+    /// A segment that becomes read-only after relocation (`PT_GNU_RELRO`).
+    pub const GNU_RELRO: Self = Self::Other(0x6474e552);
+    /// A segment that contains GNU program properties (`PT_GNU_PROPERTY`).
+    pub const GNU_PROPERTY: Self = Self::Other(0x6474e553);
+    /// A segment that contains stack trace information (`PT_GNU_SFRAME`).
+    pub const GNU_SFRAME: Self = Self::Other(0x6474e554);
+
     /// Cast to `u32`.
     pub const fn as_u32(self) -> u32 {
         self.as_number()
@@ -293,7 +322,7 @@ define_infallible_enum! {
     (InitArraySize, 27),
     (FiniArraySize, 28),
     (Runpath, 29, "String table offset to the library search path."),
-    (Flags, 30),
+    (Flags, 30, "Flags, see [`DynamicFlags`](crate::DynamicFlags)."),
     (PreInitArray, 32),
     (PreInitArraySize, 33),
     (SymbolTableIndex, 34),
@@ -302,11 +331,71 @@ define_infallible_enum! {
     (RelrEntrySize, 37, "Relative relocation entry size."),
 }
 
+/// The first tag which value is an address (`DT_ADDRRNGLO`).
+const ADDRESS_RANGE_START: u32 = 0x6ffffe00;
+/// The last tag which value is an address (`DT_ADDRRNGHI`).
+const ADDRESS_RANGE_END: u32 = 0x6ffffeff;
+
+// OS-specific tags. Variants would change how the existing `Other` values decode.
 impl DynamicTag {
+    /// The address of the GNU-style symbol hash table (`DT_GNU_HASH`).
+    pub const GNU_HASH: Self = Self::Other(0x6ffffef5);
+    /// String table offset to the configuration file (`DT_CONFIG`).
+    pub const CONFIG: Self = Self::Other(0x6ffffefa);
+    /// String table offset to the dependency audit library (`DT_DEPAUDIT`).
+    pub const DEPAUDIT: Self = Self::Other(0x6ffffefb);
+    /// String table offset to the audit library (`DT_AUDIT`).
+    pub const AUDIT: Self = Self::Other(0x6ffffefc);
+    /// The address of the symbol version table (`DT_VERSYM`).
+    pub const VERSYM: Self = Self::Other(0x6ffffff0);
+    /// Extended flags, see [`DynamicFlags1`](crate::DynamicFlags1) (`DT_FLAGS_1`).
+    pub const FLAGS_1: Self = Self::Other(0x6ffffffb);
+    /// The address of the symbol version definitions (`DT_VERDEF`).
+    pub const VERDEF: Self = Self::Other(0x6ffffffc);
+    /// The address of the symbol version requirements (`DT_VERNEED`).
+    pub const VERNEED: Self = Self::Other(0x6ffffffe);
+    /// String table offset to the auxiliary filtee (`DT_AUXILIARY`).
+    pub const AUXILIARY: Self = Self::Other(0x7ffffffd);
+    /// String table offset to the filtee (`DT_FILTER`).
+    pub const FILTER: Self = Self::Other(0x7fffffff);
+
     /// Cast to `u32`.
     pub const fn as_u32(self) -> u32 {
         self.as_number()
     }
+
+    /// Returns `true` if the value of the entry is an offset in the dynamic string table.
+    pub const fn is_string_offset(self) -> bool {
+        use DynamicTag::*;
+        matches!(
+            self,
+            Needed
+                | SharedObjectName
+                | Rpath
+                | Runpath
+                | Self::CONFIG
+                | Self::DEPAUDIT
+                | Self::AUDIT
+                | Self::AUXILIARY
+                | Self::FILTER
+        )
+    }
+
+    /// Returns `true` if the value of the entry is a virtual address.
+    pub const fn is_address(self) -> bool {
+        use DynamicTag::*;
+        match self {
+            PltGot | Hash | StringTableAddress | SymbolTableAddress | RelaTableAddress
+            | InitAddress | FiniAddress | RelTableAddress | JmpRel | InitArray | FiniArray
+            | PreInitArray | SymbolTableIndex | RelrTableAddress => true,
+            Self::VERSYM | Self::VERDEF | Self::VERNEED => true,
+            // These are string offsets despite being in the address range.
+            Self::CONFIG | Self::DEPAUDIT | Self::AUDIT => false,
+            // Includes `DT_GNU_HASH`.
+            Other(ADDRESS_RANGE_START..=ADDRESS_RANGE_END) => true,
+            _ => false,
+        }
+    }
 }
 
 impl TryFrom<u64> for DynamicTag {
@@ -340,7 +429,17 @@ define_infallible_enum! {
     (RelrTable, 19, "Relative relocation entries."),
 }
 
+// Not variants to keep the values decoding as `Other`.
 impl SectionKind {
+    /// GNU-style symbol hash table (`SHT_GNU_HASH`).
+    pub const GNU_HASH: Self = Self::Other(0x6ffffff6);
+    /// Symbol version definitions (`SHT_GNU_verdef`).
+    pub const GNU_VERDEF: Self = Self::Other(0x6ffffffd);
+    /// Symbol version requirements (`SHT_GNU_verneed`).
+    pub const GNU_VERNEED: Self = Self::Other(0x6ffffffe);
+    /// Symbol version table (`SHT_GNU_versym`).
+    pub const GNU_VERSYM: Self = Self::Other(0x6fffffff);
+
     /// Cast to `u32`.
     pub const fn as_u32(self) -> u32 {
         self.as_number()
@@ -363,6 +462,8 @@ pub enum SymbolVisibility {
     Protected = 3,
 }
 
+define_enum_serde!(SymbolVisibility, u8, Default, Internal, Hidden, Protected,);
+
 impl SymbolVisibility {
     /// Get visibility from symbol's `other` field.
     pub const fn from_other(other: u8) -> Self {
@@ -429,3 +530,39 @@ impl SymbolKind {
         self.as_u8() & 0xf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_tag_is_either_address_or_string_offset() {
+        for n in (0..=0x100)
+            .chain(0x6ffff000..=0x70000000)
+            .chain(0x7fff_f000..=0x7fff_ffff)
+        {
+            let tag: DynamicTag = n.into();
+            assert!(
+                !(tag.is_address() && tag.is_string_offset()),
+                "{tag:?} is both an address and a string offset"
+            );
+        }
+        assert!(DynamicTag::GNU_HASH.is_address());
+        assert!(!DynamicTag::AUDIT.is_address());
+        assert_eq!(DynamicTag::Other(0x6ffffffb), DynamicTag::from(0x6ffffffb));
+    }
+
+    #[test]
+    fn os_specific_kinds_decode_as_other() {
+        assert_eq!(
+            SectionKind::Other(0x6ffffff6),
+            SectionKind::from(0x6ffffff6)
+        );
+        assert_eq!(SectionKind::GNU_HASH, SectionKind::from(0x6ffffff6));
+        assert_eq!(
+            SegmentKind::Other(0x6474e551),
+            SegmentKind::from(0x6474e551)
+        );
+        assert_eq!(SegmentKind::GNU_STACK, SegmentKind::from(0x6474e551));
+    }
+}
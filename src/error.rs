@@ -1,5 +1,6 @@
 use thiserror::Error;
 
+use crate::Machine;
 use crate::SectionKind;
 use crate::SegmentKind;
 
@@ -23,6 +24,8 @@ pub enum Error {
     InvalidSectionHeaderStringTableIndex(u16),
     #[error("Invalid entry point: {0:#x}")]
     InvalidEntryPoint(u64),
+    #[error("Flags for {0:?} can't be used for {1:?}")]
+    MachineMismatch(Machine, Machine),
     #[error("Invalid PHDR segment: {0}")]
     InvalidProgramHeaderSegment(&'static str),
     #[error("Invalid file kind: {0}")]
@@ -31,6 +34,8 @@ pub enum Error {
     InvalidSegmentKind(u32),
     #[error("Invalid segment size: {0}")]
     InvalidSegmentLen(u16),
+    #[error("Invalid segment index: {0}")]
+    InvalidSegmentIndex(usize),
     #[error("Invalid section kind: {0}")]
     InvalidSectionKind(u32),
     #[error("Invalid section size: {0}")]
@@ -76,14 +81,28 @@ pub enum Error {
     SegmentAlloc,
     #[error("Failed to allocate in-file space")]
     FileSpaceAlloc,
+    #[error("New value doesn't fit in place: {0}")]
+    DoesNotFit(&'static str),
     #[error("Input/output error: {0}")]
     #[cfg(feature = "std")]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     Io(std::io::Error),
+    #[error("Failed to preserve {0}: {1}")]
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    PreserveMetadata(alloc::string::String, std::io::Error),
+    #[error("File has {0} hard links that would be broken by the replacement")]
+    HardLinks(u64),
     #[error("Invalid C-string")]
     CStr,
     #[error("Unexpected EOF")]
     UnexpectedEof,
+    #[error("Limit exceeded: {0}: {1} > {2}")]
+    LimitExceeded(&'static str, u64, u64),
+    #[error("Section {0} is referenced by {1}")]
+    SectionInUse(usize, &'static str),
+    #[error("Out of file bounds: {0:#x}..{1:#x}, file length = {2:#x}")]
+    OutOfFileBounds(u64, u64, u64),
 }
 
 #[cfg(feature = "std")]
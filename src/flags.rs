@@ -1,4 +1,9 @@
 use bitflags::bitflags;
+use bitflags::Flags;
+
+use crate::define_enum_serde;
+use crate::define_flags_serde;
+use crate::Machine;
 
 bitflags! {
     /// Segment flags.
@@ -88,6 +93,82 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Dynamic table flags.
+    ///
+    /// The value of [`FLAGS`](crate::DynamicTag::Flags) dynamic table entry.
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    pub struct DynamicFlags: u64 {
+        /// The object may reference `$ORIGIN`.
+        const ORIGIN = 0x1;
+        /// Symbol resolution starts from the object itself.
+        const SYMBOLIC = 0x2;
+        /// Relocations may modify non-writable segments.
+        const TEXTREL = 0x4;
+        /// Process all relocations before transferring control to the program.
+        const BIND_NOW = 0x8;
+        /// The object uses static thread-local storage.
+        const STATIC_TLS = 0x10;
+        // Any bits can be set.
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// Extended dynamic table flags.
+    ///
+    /// The value of [`FLAGS_1`](crate::DynamicTag::FLAGS_1) dynamic table entry.
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    pub struct DynamicFlags1: u64 {
+        /// Process all relocations before transferring control to the program.
+        const NOW = 0x1;
+        /// Make the symbols available to the subsequently loaded objects.
+        const GLOBAL = 0x2;
+        /// The object is a member of a group.
+        const GROUP = 0x4;
+        /// The object can't be unloaded.
+        const NODELETE = 0x8;
+        /// Load filtees immediately.
+        const LOADFLTR = 0x10;
+        /// Run the initializers of the object first.
+        const INITFIRST = 0x20;
+        /// The object can't be loaded via `dlopen`.
+        const NOOPEN = 0x40;
+        /// The object may reference `$ORIGIN`.
+        const ORIGIN = 0x80;
+        /// Direct binding is enabled.
+        const DIRECT = 0x100;
+        /// The object interposes the symbols of all other objects except the executable.
+        const INTERPOSE = 0x400;
+        /// Ignore default library search path.
+        const NODEFLIB = 0x800;
+        /// The object can't be dumped via `dldump`.
+        const NODUMP = 0x1000;
+        /// The object is a configuration alternative.
+        const CONFALT = 0x2000;
+        /// Filtee terminates the filter's search.
+        const ENDFILTEE = 0x4000;
+        /// Displacement relocations were applied.
+        const DISPRELDNE = 0x8000;
+        /// Displacement relocations are pending.
+        const DISPRELPND = 0x10000;
+        /// Direct binding is disabled.
+        const NODIRECT = 0x20000;
+        /// The object was modified after it was built.
+        const EDITED = 0x200000;
+        /// Interposing symbols are marked individually.
+        const SYMINTPOSE = 0x800000;
+        /// Audit all objects in the process.
+        const GLOBAUDIT = 0x1000000;
+        /// The object defines singleton symbols.
+        const SINGLETON = 0x2000000;
+        /// Position-independent executable.
+        const PIE = 0x8000000;
+        // Any bits can be set.
+        const _ = !0;
+    }
+}
+
 impl RiscvFlags {
     /// Get float ABI.
     pub const fn float_abi(self) -> Option<RiscvFloatAbi> {
@@ -101,6 +182,20 @@ impl RiscvFlags {
     }
 }
 
+/// Architecture-specific flags stored in [`Header::flags`](crate::Header::flags).
+pub trait MachineFlags: Flags<Bits = u32> {
+    /// The architecture that uses these flags.
+    const MACHINE: Machine;
+}
+
+impl MachineFlags for ArmFlags {
+    const MACHINE: Machine = Machine::Arm;
+}
+
+impl MachineFlags for RiscvFlags {
+    const MACHINE: Machine = Machine::Riscv;
+}
+
 /// RISCV float ABI.
 ///
 /// Returned by [`RiscvFlags::float_abi`](RiscvFlags::float_abi).
@@ -117,4 +212,13 @@ pub enum RiscvFloatAbi {
     Quad = 0x6,
 }
 
+define_enum_serde!(RiscvFloatAbi, u8, Soft, Single, Double, Quad,);
+
+define_flags_serde!(SegmentFlags, u32);
+define_flags_serde!(SectionFlags, u64);
+define_flags_serde!(ArmFlags, u32);
+define_flags_serde!(RiscvFlags, u32);
+define_flags_serde!(DynamicFlags, u64);
+define_flags_serde!(DynamicFlags1, u64);
+
 const RISCV_FLOAT_ABI_MASK: u32 = 0x6;
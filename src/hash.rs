@@ -0,0 +1,365 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::constants::*;
+use crate::BlockRead;
+use crate::BlockWrite;
+use crate::ByteOrder;
+use crate::Class;
+use crate::ElfRead;
+use crate::ElfWrite;
+use crate::Error;
+
+/// Compute SysV hash of the symbol name.
+///
+/// This hash function is used in [`HashTable`].
+pub const fn sysv_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    let mut i = 0;
+    while i < name.len() {
+        h = (h << 4).wrapping_add(name[i] as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+        i += 1;
+    }
+    h
+}
+
+/// Compute GNU hash of the symbol name.
+///
+/// This hash function is used in [`GnuHashTable`].
+pub const fn gnu_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 5381;
+    let mut i = 0;
+    while i < name.len() {
+        h = h.wrapping_mul(33).wrapping_add(name[i] as u32);
+        i += 1;
+    }
+    h
+}
+
+/// SysV symbol hash table (`.hash` section).
+#[derive(Default)]
+#[cfg_attr(test, derive(PartialEq, Eq, Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashTable {
+    /// The index of the first symbol in each bucket.
+    pub buckets: Vec<u32>,
+    /// The index of the next symbol in the same bucket for each symbol.
+    pub chains: Vec<u32>,
+}
+
+impl HashTable {
+    /// Build the table for the symbols with the specified names.
+    ///
+    /// The names should be in the same order as the symbols in the symbol table.
+    pub fn new<'a>(num_buckets: u32, names: impl ExactSizeIterator<Item = &'a [u8]>) -> Self {
+        let mut buckets = vec![0_u32; num_buckets as usize];
+        let mut chains = vec![0_u32; names.len()];
+        if num_buckets == 0 {
+            return Self { buckets, chains };
+        }
+        let mut last = vec![0_u32; num_buckets as usize];
+        for (i, name) in names.enumerate() {
+            if i == 0 {
+                // The first symbol is always the undefined one.
+                continue;
+            }
+            let b = (sysv_hash(name) % num_buckets) as usize;
+            if buckets[b] == 0 {
+                buckets[b] = i as u32;
+            } else {
+                chains[last[b] as usize] = i as u32;
+            }
+            last[b] = i as u32;
+        }
+        Self { buckets, chains }
+    }
+
+    /// The size of the table in bytes.
+    pub fn in_file_len(&self) -> usize {
+        (2 + self.buckets.len() + self.chains.len()) * 4
+    }
+}
+
+impl BlockRead for HashTable {
+    fn read<R: ElfRead>(
+        reader: &mut R,
+        _class: Class,
+        byte_order: ByteOrder,
+        len: u64,
+    ) -> Result<Self, Error> {
+        let num_buckets = reader.read_u32(byte_order)?;
+        let num_chains = reader.read_u32(byte_order)?;
+        let expected_len = (2 + num_buckets as u64 + num_chains as u64) * 4;
+        if expected_len > len {
+            return Err(Error::UnexpectedEof);
+        }
+        let buckets = read_u32_vec(reader, byte_order, num_buckets)?;
+        let chains = read_u32_vec(reader, byte_order, num_chains)?;
+        Ok(Self { buckets, chains })
+    }
+}
+
+impl BlockWrite for HashTable {
+    fn write<W: ElfWrite>(
+        &self,
+        writer: &mut W,
+        _class: Class,
+        byte_order: ByteOrder,
+    ) -> Result<(), Error> {
+        writer.write_u32(byte_order, to_u32(self.buckets.len())?)?;
+        writer.write_u32(byte_order, to_u32(self.chains.len())?)?;
+        for value in self.buckets.iter().chain(self.chains.iter()) {
+            writer.write_u32(byte_order, *value)?;
+        }
+        Ok(())
+    }
+}
+
+/// GNU symbol hash table (`.gnu.hash` section).
+///
+/// Only the symbols starting from [`symbol_offset`](Self::symbol_offset) are hashed, and these
+/// symbols have to be sorted by their bucket index in the symbol table.
+#[derive(Default)]
+#[cfg_attr(test, derive(PartialEq, Eq, Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GnuHashTable {
+    /// The index of the first hashed symbol.
+    pub symbol_offset: u32,
+    /// Bloom filter shift.
+    pub bloom_shift: u32,
+    /// Bloom filter words.
+    ///
+    /// Each word is 32-bit or 64-bit wide depending on the class.
+    pub bloom: Vec<u64>,
+    /// The index of the first symbol in each bucket.
+    pub buckets: Vec<u32>,
+    /// Symbol hashes with the lowest bit marking the last symbol in the bucket.
+    pub chains: Vec<u32>,
+}
+
+impl GnuHashTable {
+    /// Get the bucket index of the symbol with the specified name hash.
+    ///
+    /// Returns `None` if the table has no buckets.
+    pub fn bucket(&self, hash: u32) -> Option<u32> {
+        let num_buckets = self.buckets.len() as u32;
+        (num_buckets != 0).then(|| hash % num_buckets)
+    }
+
+    /// Rebuild the table for the symbols with the specified name hashes.
+    ///
+    /// The hashes should be in the same order as the symbols in the symbol table starting from
+    /// [`symbol_offset`](Self::symbol_offset). The symbols should be sorted by their bucket
+    /// index (see [`bucket`](Self::bucket)). The number of buckets and the size of the bloom
+    /// filter are preserved.
+    pub fn rebuild(&mut self, class: Class, hashes: &[u32]) {
+        let word_bits = match class {
+            Class::Elf32 => 32,
+            Class::Elf64 => 64,
+        };
+        self.bloom.fill(0);
+        self.buckets.fill(0);
+        self.chains.clear();
+        self.chains.reserve(hashes.len());
+        let num_bloom_words = self.bloom.len() as u32;
+        for (i, hash) in hashes.iter().enumerate() {
+            if num_bloom_words != 0 {
+                let word = ((hash / word_bits) % num_bloom_words) as usize;
+                self.bloom[word] |= (1_u64 << (hash % word_bits))
+                    | (1_u64 << (hash.checked_shr(self.bloom_shift).unwrap_or(0) % word_bits));
+            }
+            let Some(bucket) = self.bucket(*hash) else {
+                continue;
+            };
+            let bucket = &mut self.buckets[bucket as usize];
+            if *bucket == 0 {
+                *bucket = self.symbol_offset + i as u32;
+                if let Some(last) = self.chains.last_mut() {
+                    // Terminate the chain of the previous bucket.
+                    *last |= 1;
+                }
+            }
+            self.chains.push(hash & !1);
+        }
+        if let Some(last) = self.chains.last_mut() {
+            *last |= 1;
+        }
+    }
+
+    /// The size of the table in bytes.
+    pub fn in_file_len(&self, class: Class) -> usize {
+        let word_len = match class {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        };
+        (4 + self.buckets.len() + self.chains.len()) * 4 + self.bloom.len() * word_len
+    }
+}
+
+impl BlockRead for GnuHashTable {
+    fn read<R: ElfRead>(
+        reader: &mut R,
+        class: Class,
+        byte_order: ByteOrder,
+        len: u64,
+    ) -> Result<Self, Error> {
+        let num_buckets = reader.read_u32(byte_order)?;
+        let symbol_offset = reader.read_u32(byte_order)?;
+        let num_bloom_words = reader.read_u32(byte_order)?;
+        let bloom_shift = reader.read_u32(byte_order)?;
+        let word_len = match class {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        };
+        let header_len = 16 + num_bloom_words as u64 * word_len + num_buckets as u64 * 4;
+        if header_len > len {
+            return Err(Error::UnexpectedEof);
+        }
+        let mut bloom = Vec::with_capacity((num_bloom_words as usize).min(READ_CHUNK_LEN));
+        for _ in 0..num_bloom_words {
+            bloom.push(reader.read_word(class, byte_order)?);
+        }
+        let buckets = read_u32_vec(reader, byte_order, num_buckets)?;
+        let num_chains = to_u32(((len - header_len) / 4) as usize)?;
+        let chains = read_u32_vec(reader, byte_order, num_chains)?;
+        Ok(Self {
+            symbol_offset,
+            bloom_shift,
+            bloom,
+            buckets,
+            chains,
+        })
+    }
+}
+
+impl BlockWrite for GnuHashTable {
+    fn write<W: ElfWrite>(
+        &self,
+        writer: &mut W,
+        class: Class,
+        byte_order: ByteOrder,
+    ) -> Result<(), Error> {
+        writer.write_u32(byte_order, to_u32(self.buckets.len())?)?;
+        writer.write_u32(byte_order, self.symbol_offset)?;
+        writer.write_u32(byte_order, to_u32(self.bloom.len())?)?;
+        writer.write_u32(byte_order, self.bloom_shift)?;
+        for word in self.bloom.iter() {
+            writer.write_word(class, byte_order, *word)?;
+        }
+        for value in self.buckets.iter().chain(self.chains.iter()) {
+            writer.write_u32(byte_order, *value)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32_vec<R: ElfRead>(
+    reader: &mut R,
+    byte_order: ByteOrder,
+    len: u32,
+) -> Result<Vec<u32>, Error> {
+    // The length comes from the file and can't be trusted, hence the upper bound.
+    let mut values = Vec::with_capacity((len as usize).min(READ_CHUNK_LEN / 4));
+    for _ in 0..len {
+        values.push(reader.read_u32(byte_order)?);
+    }
+    Ok(values)
+}
+
+fn to_u32(n: usize) -> Result<u32, Error> {
+    n.try_into().map_err(|_| Error::TooBig("Hash table"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arbitrary::Unstructured;
+
+    use crate::test::test_block_io;
+    use crate::test::ArbitraryWithClass;
+
+    #[test]
+    fn hash_functions() {
+        assert_eq!(0, sysv_hash(b""));
+        assert_eq!(0x077905a6, sysv_hash(b"printf"));
+        assert_eq!(0x0006cf04, sysv_hash(b"exit"));
+        assert_eq!(0x00001505, gnu_hash(b""));
+        assert_eq!(0x156b2bb8, gnu_hash(b"printf"));
+        assert_eq!(0x7c967e3f, gnu_hash(b"exit"));
+    }
+
+    #[test]
+    fn gnu_hash_table_rebuild() {
+        let names: [&[u8]; 3] = [b"a", b"b", b"c"];
+        let mut hashes: Vec<u32> = names.iter().map(|name| gnu_hash(name)).collect();
+        let mut table = GnuHashTable {
+            symbol_offset: 1,
+            bloom_shift: 6,
+            bloom: vec![0; 1],
+            buckets: vec![0; 2],
+            chains: Vec::new(),
+        };
+        hashes.sort_by_key(|hash| table.bucket(*hash));
+        table.rebuild(Class::Elf64, &hashes);
+        assert_eq!(3, table.chains.len());
+        for (i, hash) in hashes.iter().enumerate() {
+            let bucket = table.bucket(*hash).unwrap() as usize;
+            let index = table.symbol_offset + i as u32;
+            let mut j = table.buckets[bucket];
+            assert_ne!(0, j);
+            // Walk the chain.
+            while j != index {
+                let value = table.chains[(j - table.symbol_offset) as usize];
+                assert_eq!(0, value & 1, "Symbol {i} not found");
+                j += 1;
+            }
+            let value = table.chains[(j - table.symbol_offset) as usize];
+            assert_eq!(hash | 1, value | 1);
+        }
+        assert_ne!(0, table.bloom[0]);
+    }
+
+    #[test]
+    fn hash_table_io() {
+        test_block_io::<HashTable>();
+    }
+
+    #[test]
+    fn gnu_hash_table_io() {
+        test_block_io::<GnuHashTable>();
+    }
+
+    impl ArbitraryWithClass<'_> for HashTable {
+        fn arbitrary(u: &mut Unstructured<'_>, _class: Class) -> arbitrary::Result<Self> {
+            Ok(Self {
+                buckets: u.arbitrary()?,
+                chains: u.arbitrary()?,
+            })
+        }
+    }
+
+    impl ArbitraryWithClass<'_> for GnuHashTable {
+        fn arbitrary(u: &mut Unstructured<'_>, class: Class) -> arbitrary::Result<Self> {
+            let bloom: Vec<u64> = match class {
+                Class::Elf32 => {
+                    let bloom: Vec<u32> = u.arbitrary()?;
+                    bloom.into_iter().map(Into::into).collect()
+                }
+                Class::Elf64 => u.arbitrary()?,
+            };
+            Ok(Self {
+                symbol_offset: u.arbitrary()?,
+                bloom_shift: u.arbitrary()?,
+                bloom,
+                buckets: u.arbitrary()?,
+                chains: u.arbitrary()?,
+            })
+        }
+    }
+}
@@ -12,8 +12,9 @@ use crate::Machine;
 use crate::OsAbi;
 
 /// ELF header.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// Bitness.
     pub class: Class,
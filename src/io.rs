@@ -1,7 +1,7 @@
-use alloc::vec;
 use alloc::vec::Vec;
 use core::ffi::CStr;
 
+use crate::constants::*;
 use crate::ByteOrder;
 use crate::Class;
 use crate::Error;
@@ -80,7 +80,7 @@ impl ElfRead for &[u8] {
         if n > self.len() {
             return Err(Error::UnexpectedEof);
         }
-        buf.copy_from_slice(self[..n]);
+        buf.copy_from_slice(&self[..n]);
         *self = &self[n..];
         Ok(())
     }
@@ -178,10 +178,26 @@ impl<W: std::io::Write + ?Sized> ElfWrite for W {
     }
 }
 
+#[cfg(not(feature = "std"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "std"))))]
+impl ElfWrite for Vec<u8> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
 /// ELF-specific seek functions.
 pub trait ElfSeek {
     /// Seek to the specified offset from the start of the file.
     fn seek(&mut self, offset: u64) -> Result<(), Error>;
+
+    /// Get the file length in bytes.
+    ///
+    /// Returns `None` if the length is unknown.
+    fn file_len(&mut self) -> Result<Option<u64>, Error> {
+        Ok(None)
+    }
 }
 
 #[cfg(feature = "std")]
@@ -191,6 +207,286 @@ impl<S: std::io::Seek + ?Sized> ElfSeek for S {
         self.seek(std::io::SeekFrom::Start(offset))?;
         Ok(())
     }
+
+    fn file_len(&mut self) -> Result<Option<u64>, Error> {
+        let old_offset = self.stream_position()?;
+        let len = self.seek(std::io::SeekFrom::End(0))?;
+        if old_offset != len {
+            self.seek(std::io::SeekFrom::Start(old_offset))?;
+        }
+        Ok(Some(len))
+    }
+}
+
+/// ELF-specific truncate functions.
+pub trait ElfTruncate {
+    /// Change the file length to `len` bytes.
+    ///
+    /// Similar to [`File::set_len`](std::fs::File::set_len).
+    fn truncate(&mut self, len: u64) -> Result<(), Error>;
+}
+
+impl<T: ElfTruncate + ?Sized> ElfTruncate for &mut T {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        (**self).truncate(len)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ElfTruncate for std::fs::File {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.set_len(len)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl ElfTruncate for std::io::Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        truncate_vec(self.get_mut(), len)
+    }
+}
+
+/// In-memory file.
+///
+/// Implements [`ElfRead`] and [`ElfSeek`] for any byte buffer, and [`ElfWrite`] for `Vec<u8>`
+/// and `&mut [u8]`. Works without `std`.
+///
+/// Writing past the end of `Vec<u8>` extends the vector, and the gap is filled with zeroes.
+/// Writing past the end of `&mut [u8]` fails.
+#[derive(Debug, Default, Clone)]
+pub struct Cursor<T> {
+    inner: T,
+    position: u64,
+}
+
+impl<T> Cursor<T> {
+    /// Create new cursor that points to the start of the buffer.
+    pub const fn new(inner: T) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Get the current position in the buffer.
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Get the underlying buffer.
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the underlying buffer.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Get the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsRef<[u8]>> ElfRead for Cursor<T> {
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.inner.as_ref().read_bytes_at(buf, self.position)?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>> ElfSeek for Cursor<T> {
+    fn seek(&mut self, offset: u64) -> Result<(), Error> {
+        self.position = offset;
+        Ok(())
+    }
+
+    fn file_len(&mut self) -> Result<Option<u64>, Error> {
+        Ok(Some(self.inner.as_ref().len() as u64))
+    }
+}
+
+impl ElfWrite for Cursor<Vec<u8>> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        write_to_vec(&mut self.inner, &mut self.position, bytes)
+    }
+}
+
+impl ElfWrite for Cursor<&mut Vec<u8>> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        write_to_vec(self.inner, &mut self.position, bytes)
+    }
+}
+
+impl ElfWrite for Cursor<&mut [u8]> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let start: usize = self
+            .position
+            .try_into()
+            .map_err(|_| Error::TooBig("Write offset"))?;
+        let end = start
+            .checked_add(bytes.len())
+            .ok_or(Error::TooBig("Write offset"))?;
+        let buf = self
+            .inner
+            .get_mut(start..end)
+            .ok_or(Error::TooBig("Write offset"))?;
+        buf.copy_from_slice(bytes);
+        self.position = end as u64;
+        Ok(())
+    }
+}
+
+impl ElfTruncate for Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        truncate_vec(&mut self.inner, len)
+    }
+}
+
+impl ElfTruncate for Cursor<&mut Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        truncate_vec(self.inner, len)
+    }
+}
+
+fn truncate_vec(vec: &mut Vec<u8>, len: u64) -> Result<(), Error> {
+    let len: usize = len.try_into().map_err(|_| Error::TooBig("File length"))?;
+    vec.resize(len, 0_u8);
+    Ok(())
+}
+
+fn write_to_vec(vec: &mut Vec<u8>, position: &mut u64, bytes: &[u8]) -> Result<(), Error> {
+    let start: usize = (*position)
+        .try_into()
+        .map_err(|_| Error::TooBig("Write offset"))?;
+    let end = start
+        .checked_add(bytes.len())
+        .ok_or(Error::TooBig("Write offset"))?;
+    if vec.len() < end {
+        vec.resize(end, 0_u8);
+    }
+    vec[start..end].copy_from_slice(bytes);
+    *position = end as u64;
+    Ok(())
+}
+
+/// ELF-specific positional read functions.
+///
+/// Unlike [`ElfRead`] these functions don't require mutable access and don't modify the current
+/// position in the file. This allows sharing one file between multiple threads.
+///
+/// Use [`ReadAtCursor`] to pass an implementation of this trait to the functions that expect
+/// [`ElfRead`] and [`ElfSeek`].
+pub trait ElfReadAt {
+    /// Read enough bytes to fill the buffer `buf` starting from the specified `offset`.
+    ///
+    /// Similar to [`FileExt::read_exact_at`](std::os::unix::fs::FileExt::read_exact_at).
+    fn read_bytes_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error>;
+
+    /// Get the file length in bytes.
+    ///
+    /// Returns `None` if the length is unknown.
+    fn file_len(&self) -> Result<Option<u64>, Error> {
+        Ok(None)
+    }
+}
+
+impl ElfReadAt for [u8] {
+    fn read_bytes_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        let start: usize = offset.try_into().map_err(|_| Error::UnexpectedEof)?;
+        let end = start.checked_add(buf.len()).ok_or(Error::UnexpectedEof)?;
+        let bytes = self.get(start..end).ok_or(Error::UnexpectedEof)?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn file_len(&self) -> Result<Option<u64>, Error> {
+        Ok(Some(self.len() as u64))
+    }
+}
+
+impl ElfReadAt for Vec<u8> {
+    fn read_bytes_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        self.as_slice().read_bytes_at(buf, offset)
+    }
+
+    fn file_len(&self) -> Result<Option<u64>, Error> {
+        self.as_slice().file_len()
+    }
+}
+
+impl<T: ElfReadAt + ?Sized> ElfReadAt for &T {
+    fn read_bytes_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        (**self).read_bytes_at(buf, offset)
+    }
+
+    fn file_len(&self) -> Result<Option<u64>, Error> {
+        (**self).file_len()
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "std", unix))))]
+impl ElfReadAt for std::fs::File {
+    fn read_bytes_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        use std::os::unix::fs::FileExt;
+        Ok(self.read_exact_at(buf, offset)?)
+    }
+
+    fn file_len(&self) -> Result<Option<u64>, Error> {
+        Ok(Some(self.metadata()?.len()))
+    }
+}
+
+/// Implements [`ElfRead`] and [`ElfSeek`] on top of [`ElfReadAt`].
+///
+/// The cursor stores its own position in the file, and the underlying file is only borrowed
+/// immutably. Create one cursor per thread to read the same file in parallel.
+#[derive(Debug)]
+pub struct ReadAtCursor<'a, R: ?Sized> {
+    reader: &'a R,
+    offset: u64,
+}
+
+impl<'a, R: ElfReadAt + ?Sized> ReadAtCursor<'a, R> {
+    /// Create new cursor that points to the start of the file.
+    pub const fn new(reader: &'a R) -> Self {
+        Self { reader, offset: 0 }
+    }
+
+    /// Get the current position in the file.
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the underlying file.
+    pub const fn get_ref(&self) -> &'a R {
+        self.reader
+    }
+}
+
+impl<R: ElfReadAt + ?Sized> ElfRead for ReadAtCursor<'_, R> {
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.reader.read_bytes_at(buf, self.offset)?;
+        self.offset = self
+            .offset
+            .checked_add(buf.len() as u64)
+            .ok_or(Error::UnexpectedEof)?;
+        Ok(())
+    }
+}
+
+impl<R: ElfReadAt + ?Sized> ElfSeek for ReadAtCursor<'_, R> {
+    fn seek(&mut self, offset: u64) -> Result<(), Error> {
+        self.offset = offset;
+        Ok(())
+    }
+
+    fn file_len(&mut self) -> Result<Option<u64>, Error> {
+        self.reader.file_len()
+    }
 }
 
 /// Read an entity from a file or write an entity to a file.
@@ -239,6 +535,11 @@ pub trait BlockWrite {
     ) -> Result<(), Error>;
 }
 
+/// Reads exactly `len` bytes.
+///
+/// The bytes are read in chunks, so that a length that exceeds the file size results in
+/// [`Error::UnexpectedEof`] instead of a huge allocation. Other than that `len` isn't checked
+/// against [`Limits`](crate::Limits).
 impl BlockRead for Vec<u8> {
     fn read<R: ElfRead>(
         reader: &mut R,
@@ -246,11 +547,24 @@ impl BlockRead for Vec<u8> {
         _byte_order: ByteOrder,
         len: u64,
     ) -> Result<Self, Error> {
-        let n: usize = len.try_into().map_err(|_| Error::TooBig("Block size"))?;
-        let mut buf = vec![0_u8; n];
-        reader.read_bytes(&mut buf[..])?;
-        Ok(buf)
+        read_bytes_chunked(reader, len)
+    }
+}
+
+/// Read `len` bytes in chunks.
+///
+/// The length is usually read from the file itself and can't be trusted. Reading in chunks
+/// ensures that we fail with [`Error::UnexpectedEof`] before allocating the whole block.
+pub(crate) fn read_bytes_chunked<R: ElfRead>(reader: &mut R, len: u64) -> Result<Vec<u8>, Error> {
+    let n: usize = len.try_into().map_err(|_| Error::TooBig("Block size"))?;
+    let mut buf = Vec::with_capacity(n.min(READ_CHUNK_LEN));
+    while buf.len() != n {
+        let offset = buf.len();
+        let chunk_len = (n - offset).min(READ_CHUNK_LEN);
+        buf.resize(offset + chunk_len, 0_u8);
+        reader.read_bytes(&mut buf[offset..])?;
     }
+    Ok(buf)
 }
 
 impl<T: AsRef<[u8]>> BlockWrite for T {
@@ -296,3 +610,58 @@ pub(crate) fn write_zeroes<W: ElfWrite + ElfSeek>(writer: &mut W, size: u64) ->
     }
     Ok(())
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Elf;
+    use crate::SectionKind;
+    use fs_err::File;
+
+    #[test]
+    fn read_at_from_multiple_threads() {
+        let mut file = File::open(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::read(&mut file, 4096).unwrap();
+        let expected: Vec<Vec<u8>> = elf
+            .sections
+            .iter()
+            .map(|section| {
+                if section.kind == SectionKind::NoBits {
+                    return Vec::new();
+                }
+                section
+                    .read_content(&mut file, elf.header.class, elf.header.byte_order)
+                    .unwrap()
+            })
+            .collect();
+        let file = file.file();
+        let elf_at = Elf::read_at(file, 4096).unwrap();
+        assert_eq!(elf.header, elf_at.header);
+        std::thread::scope(|scope| {
+            for (section, expected) in elf_at.sections.iter().zip(expected.iter()) {
+                if section.kind == SectionKind::NoBits {
+                    continue;
+                }
+                let class = elf_at.header.class;
+                let byte_order = elf_at.header.byte_order;
+                scope.spawn(move || {
+                    let actual: Vec<u8> = section.read_content_at(file, class, byte_order).unwrap();
+                    assert_eq!(expected, &actual);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn read_at_slice() {
+        let bytes = [1_u8, 2, 3, 4];
+        let mut cursor = ReadAtCursor::new(&bytes[..]);
+        cursor.seek(1).unwrap();
+        let mut buf = [0_u8; 2];
+        cursor.read_bytes(&mut buf).unwrap();
+        assert_eq!([2, 3], buf);
+        assert_eq!(3, cursor.offset());
+        assert!(cursor.read_bytes(&mut buf).is_err());
+        assert!(bytes[..].read_bytes_at(&mut buf, u64::MAX).is_err());
+    }
+}
@@ -7,17 +7,24 @@ extern crate alloc;
 extern crate std;
 
 mod allocator;
+mod blob;
+mod builder;
 mod byte_order;
 mod class;
 pub(crate) mod constants;
+mod debuglink;
+mod diff;
+mod dry_run;
 mod dynamic_table;
 mod elf;
 mod enums;
 mod error;
 mod flags;
+mod hash;
 mod header;
 pub mod host;
 mod io;
+mod limits;
 mod macros;
 mod patch;
 mod relocations;
@@ -27,17 +34,26 @@ mod strings;
 mod symbols;
 #[cfg(test)]
 pub(crate) mod test;
+#[cfg(feature = "std")]
+mod transaction;
 
 pub use self::allocator::*;
+pub use self::blob::*;
+pub use self::builder::*;
 pub use self::byte_order::*;
 pub use self::class::*;
+pub use self::debuglink::*;
+pub use self::diff::*;
+pub use self::dry_run::*;
 pub use self::dynamic_table::*;
 pub use self::elf::*;
 pub use self::enums::*;
 pub use self::error::*;
 pub use self::flags::*;
+pub use self::hash::*;
 pub use self::header::*;
 pub use self::io::*;
+pub use self::limits::*;
 pub(crate) use self::macros::*;
 pub use self::patch::*;
 pub use self::relocations::*;
@@ -45,3 +61,6 @@ pub use self::sections::*;
 pub use self::segments::*;
 pub use self::strings::*;
 pub use self::symbols::*;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use self::transaction::*;
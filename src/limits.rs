@@ -0,0 +1,130 @@
+use crate::Error;
+
+/// Limits that are checked when reading ELF files.
+///
+/// Use these limits when reading untrusted files to prevent excessive memory allocations.
+/// All limits are disabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// The maximum size in bytes of a section or a segment which contents are read into memory.
+    pub max_section_size: u64,
+    /// The maximum number of sections.
+    pub max_sections: u64,
+    /// The maximum number of segments.
+    pub max_segments: u64,
+    /// The maximum size in bytes of a string table.
+    pub max_string_table_size: u64,
+}
+
+impl Limits {
+    /// No limits.
+    pub const fn unlimited() -> Self {
+        Self {
+            max_section_size: u64::MAX,
+            max_sections: u64::MAX,
+            max_segments: u64::MAX,
+            max_string_table_size: u64::MAX,
+        }
+    }
+
+    pub(crate) fn check_section_size(&self, size: u64) -> Result<(), Error> {
+        check_limit(size, self.max_section_size, "Section size")
+    }
+
+    pub(crate) fn check_num_sections(&self, num_sections: u64) -> Result<(), Error> {
+        check_limit(num_sections, self.max_sections, "No. of sections")
+    }
+
+    pub(crate) fn check_num_segments(&self, num_segments: u64) -> Result<(), Error> {
+        check_limit(num_segments, self.max_segments, "No. of segments")
+    }
+
+    pub(crate) fn check_string_table_size(&self, size: u64) -> Result<(), Error> {
+        check_limit(size, self.max_string_table_size, "String table size")
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+fn check_limit(value: u64, max: u64, name: &'static str) -> Result<(), Error> {
+    if value > max {
+        return Err(Error::LimitExceeded(name, value, max));
+    }
+    Ok(())
+}
+
+/// Check that `size` bytes starting from `offset` are within the file.
+pub(crate) fn check_file_bounds(offset: u64, size: u64, file_len: u64) -> Result<(), Error> {
+    match offset.checked_add(size) {
+        Some(end) if end <= file_len => Ok(()),
+        _ => Err(Error::OutOfFileBounds(
+            offset,
+            offset.saturating_add(size),
+            file_len,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Elf;
+    use fs_err::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn limits_are_checked() {
+        let mut file = File::open(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::read(&mut file, 4096).unwrap();
+        let limits = Limits {
+            max_sections: elf.header.num_sections as u64 - 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Elf::read_with_limits(&mut file, 4096, limits),
+            Err(Error::LimitExceeded(..))
+        ));
+        let limits = Limits {
+            max_segments: elf.header.num_segments as u64 - 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Elf::read_with_limits(&mut file, 4096, limits),
+            Err(Error::LimitExceeded(..))
+        ));
+        let limits = Limits {
+            max_string_table_size: 0,
+            ..Default::default()
+        };
+        let elf = Elf::read_with_limits(&mut file, 4096, limits).unwrap();
+        assert!(matches!(
+            elf.read_section_names(&mut file),
+            Err(Error::LimitExceeded(..))
+        ));
+    }
+
+    #[test]
+    fn file_bounds_are_checked() {
+        let mut bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::read(&mut Cursor::new(&bytes), 4096).unwrap();
+        let section_header_end = elf.header.section_header_offset + elf.header.section_header_len();
+        bytes.truncate(section_header_end as usize - 1);
+        assert!(matches!(
+            Elf::read_unchecked(&mut Cursor::new(&bytes), 4096),
+            Err(Error::OutOfFileBounds(..))
+        ));
+    }
+
+    #[test]
+    fn huge_blocks_are_not_allocated() {
+        let bytes = [0_u8; 16];
+        assert!(matches!(
+            crate::read_bytes_chunked(&mut &bytes[..], u32::MAX as u64),
+            Err(Error::UnexpectedEof)
+        ));
+    }
+}
@@ -35,6 +35,27 @@ macro_rules! define_infallible_enum {
             }
         }
 
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl ::serde::Serialize for $enum {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let name = match self {
+                    $( Self::$name => Some(stringify!($name)), )*
+                    Self::Other(..) => None,
+                };
+                crate::macros::serialize_enum(serializer, name, self.as_number())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl<'de> ::serde::Deserialize<'de> for $enum {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let number: $int = crate::macros::deserialize_enum(deserializer)?;
+                Ok($enum::from(number))
+            }
+        }
+
         #[cfg(test)]
         impl<'a> ::arbitrary::Arbitrary<'a> for $enum {
             fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
@@ -81,7 +102,124 @@ macro_rules! define_enum_v2 {
                 }
             }
         }
+
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl ::serde::Serialize for $enum {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let name = match self {
+                    $( Self::$name => Some(stringify!($name)), )*
+                    Self::Other(..) => None,
+                };
+                crate::macros::serialize_enum(serializer, name, self.as_number())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl<'de> ::serde::Deserialize<'de> for $enum {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let number: $int = crate::macros::deserialize_enum(deserializer)?;
+                Ok($enum::from(number))
+            }
+        }
     };
 }
 
 pub(crate) use define_enum_v2;
+
+/// Implements `serde` traits for enums without `Other` variant.
+macro_rules! define_enum_serde {
+    ($enum: ident, $int: ident, $($name: ident,)*) => {
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl ::serde::Serialize for $enum {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let name = match self {
+                    $( Self::$name => stringify!($name), )*
+                };
+                crate::macros::serialize_enum(serializer, Some(name), *self as $int)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl<'de> ::serde::Deserialize<'de> for $enum {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let number: $int = crate::macros::deserialize_enum(deserializer)?;
+                match number {
+                    $( n if n == Self::$name as $int => Ok(Self::$name), )*
+                    n => Err(<D::Error as ::serde::de::Error>::custom(
+                        ::core::format_args!("invalid {} value: {}", stringify!($enum), n)
+                    )),
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use define_enum_serde;
+
+/// Implements `serde` traits for flags.
+///
+/// Flags are serialized as the list of names and the raw bits.
+/// Only raw bits are used for deserialization.
+macro_rules! define_flags_serde {
+    ($flags: ident, $int: ident) => {
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl ::serde::Serialize for $flags {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use ::serde::ser::SerializeStruct;
+                let names: ::alloc::vec::Vec<&str> =
+                    self.iter_names().map(|(name, _)| name).collect();
+                let mut state = serializer.serialize_struct(stringify!($flags), 2)?;
+                state.serialize_field("names", &names)?;
+                state.serialize_field("bits", &self.bits())?;
+                state.end()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        impl<'de> ::serde::Deserialize<'de> for $flags {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                #[derive(::serde::Deserialize)]
+                struct Flags {
+                    bits: $int,
+                }
+                let flags = <Flags as ::serde::Deserialize>::deserialize(deserializer)?;
+                Ok(Self::from_bits_retain(flags.bits))
+            }
+        }
+    };
+}
+
+pub(crate) use define_flags_serde;
+
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_enum<S: serde::Serializer, T: serde::Serialize>(
+    serializer: S,
+    name: Option<&'static str>,
+    value: T,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+    let mut state = serializer.serialize_struct("Enum", 2)?;
+    state.serialize_field("name", &name)?;
+    state.serialize_field("value", &value)?;
+    state.end()
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_enum<'de, D: serde::Deserializer<'de>, T: serde::Deserialize<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    #[derive(serde::Deserialize)]
+    struct Enum<T> {
+        value: T,
+    }
+    let value = <Enum<T> as serde::Deserialize>::deserialize(deserializer)?;
+    Ok(value.value)
+}
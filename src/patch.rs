@@ -1,20 +1,36 @@
+use alloc::collections::BTreeMap;
 use alloc::ffi::CString;
 use alloc::vec::Vec;
 use core::ffi::CStr;
+use core::ops::Range;
 use log::log_enabled;
 use log::Level;
 
+use crate::align_is_valid;
+use crate::align_up;
 use crate::constants::*;
+use crate::gnu_hash;
+use crate::io::write_zeroes;
 use crate::BlockRead;
 use crate::BlockWrite;
+use crate::Class;
+use crate::DebugLink;
 use crate::DynamicTable;
 use crate::DynamicTag;
 use crate::DynamicValue;
 use crate::Elf;
 use crate::ElfRead;
 use crate::ElfSeek;
+use crate::ElfTruncate;
 use crate::ElfWrite;
 use crate::Error;
+use crate::GnuHashTable;
+use crate::HashTable;
+use crate::Header;
+use crate::MachineFlags;
+use crate::OsAbi;
+use crate::RelTable;
+use crate::RelaTable;
 use crate::Section;
 use crate::SectionFlags;
 use crate::SectionKind;
@@ -23,15 +39,34 @@ use crate::SegmentFlags;
 use crate::SegmentKind;
 use crate::SpaceAllocator;
 use crate::StringTable;
+use crate::Symbol;
+use crate::SymbolBinding;
 use crate::SymbolTable;
+use crate::SymbolVisibility;
+
+/// How the patcher replaces the interpreter and dynamic table strings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatchPolicy {
+    /// Always write the new value to a newly allocated section.
+    #[default]
+    Relocate,
+    /// Overwrite the old value in place if the new one fits, otherwise relocate.
+    PreferInPlace,
+    /// Overwrite the old value in place, fail if the new one doesn't fit.
+    InPlaceOnly,
+}
 
 /// ELF patcher.
 ///
 /// Supports modifying the interpreter and RPATH/RUNPATH.
+/// Files without section header are patched using segments only: the new data is placed into
+/// new `LOAD` segments and no section header is added.
+/// Wrap the file in [`DryRun`](crate::DryRun) to preview the changes without modifying the file.
 pub struct ElfPatcher<F> {
     elf: Elf,
     file: F,
     page_size: u64,
+    policy: PatchPolicy,
     /// Section names.
     names: Option<StringTable>,
 }
@@ -45,6 +80,7 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
             elf,
             file,
             page_size: DEFAULT_PAGE_SIZE,
+            policy: Default::default(),
             names: None,
         }
     }
@@ -56,6 +92,15 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
         self.page_size = value;
     }
 
+    /// Change patch policy.
+    ///
+    /// The policy is used by [`set_interpreter`](Self::set_interpreter) and
+    /// [`set_dynamic_tag`](Self::set_dynamic_tag). In-place patching keeps the sections and
+    /// segments intact and pads the new value with NUL bytes.
+    pub fn set_patch_policy(&mut self, policy: PatchPolicy) {
+        self.policy = policy;
+    }
+
     /// Get the current ELF.
     pub fn elf(&self) -> &Elf {
         &self.elf
@@ -75,34 +120,73 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
         Ok(self.file)
     }
 
+    /// Finish, write the current ELF to the file and remove the unused space at the end of the file.
+    ///
+    /// Usually called after [`compact`](Self::compact).
+    pub fn finish_and_truncate(mut self) -> Result<F, Error>
+    where
+        F: ElfTruncate,
+    {
+        self.do_finish()?;
+        let len = self.file_end();
+        self.elf.write(&mut self.file)?;
+        if self.file.file_len()?.is_some_and(|file_len| file_len > len) {
+            log::trace!("Truncating the file to {len:#x} bytes");
+            self.file.truncate(len)?;
+        }
+        Ok(self.file)
+    }
+
     fn do_finish(&mut self) -> Result<(), Error> {
-        // Remove old program header.
-        if let Some(i) = self
-            .elf
-            .segments
-            .iter()
-            .position(|segment| segment.kind == SegmentKind::ProgramHeader)
-        {
-            self.free_segment(i)?;
-        }
-        // Allocate new program header.
-        let program_header_len = (self.elf.segments.len() as u64)
-            // +1 because PHDR is also a segment
-            // +1 because PHDR segment has to be covered by LOAD segment
-            .checked_add(2)
-            .ok_or(Error::TooBig("No. of segments"))?
-            .checked_mul(self.elf.header.class.segment_len() as u64)
-            .ok_or(Error::TooBig("No. of segments"))?;
-        let phdr_segment_index = self.alloc_segment(Segment {
-            kind: SegmentKind::ProgramHeader,
-            flags: SegmentFlags::READABLE,
-            virtual_address: 0,
-            physical_address: 0,
-            offset: 0,
-            file_size: program_header_len,
-            memory_size: program_header_len,
-            align: PHDR_ALIGN,
-        })?;
+        if self.policy != PatchPolicy::Relocate && self.finish_in_place()? {
+            return Ok(());
+        }
+        if self.elf.segments.is_empty() {
+            // Don't add program header if the file didn't have one, e.g. relocatable files.
+            self.elf.header.program_header_offset = 0;
+        } else {
+            // Remove old program header.
+            if let Some(i) = self
+                .elf
+                .segments
+                .iter()
+                .position(|segment| segment.kind == SegmentKind::ProgramHeader)
+            {
+                self.free_segment(i)?;
+            }
+            // Allocate new program header.
+            let program_header_len = (self.elf.segments.len() as u64)
+                // +1 because PHDR is also a segment
+                // +1 because PHDR segment has to be covered by LOAD segment
+                .checked_add(2)
+                .ok_or(Error::TooBig("No. of segments"))?
+                .checked_mul(self.elf.header.class.segment_len() as u64)
+                .ok_or(Error::TooBig("No. of segments"))?;
+            let phdr_segment_index = self.alloc_segment(Segment {
+                kind: SegmentKind::ProgramHeader,
+                flags: SegmentFlags::READABLE,
+                virtual_address: 0,
+                physical_address: 0,
+                offset: 0,
+                file_size: program_header_len,
+                memory_size: program_header_len,
+                align: PHDR_ALIGN,
+            })?;
+            let phdr = &self.elf.segments[phdr_segment_index];
+            self.elf.header.program_header_offset = phdr.offset;
+        }
+        self.elf.header.num_segments = self.elf.segments.len().try_into().unwrap_or(u16::MAX);
+        if self.elf.sections.is_empty() {
+            // Don't add section header if the file didn't have one.
+            if self.elf.header.num_segments == u16::MAX {
+                // No pseudo-section to store the number of segments in.
+                return Err(Error::TooBig("No. of segments"));
+            }
+            self.elf.header.section_header_offset = 0;
+            self.elf.header.num_sections = 0;
+            self.elf.segments.finish();
+            return Ok(());
+        }
         // Allocate new section header.
         self.elf.sections.finish();
         let section_header_len = (self.elf.sections.len() as u64)
@@ -112,9 +196,6 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
             .alloc_section_header(section_header_len)
             .ok_or(Error::FileSpaceAlloc)?;
         // Update ELF header.
-        let phdr = &self.elf.segments[phdr_segment_index];
-        self.elf.header.program_header_offset = phdr.offset;
-        self.elf.header.num_segments = self.elf.segments.len().try_into().unwrap_or(u16::MAX);
         self.elf.header.section_header_offset = section_header_offset;
         self.elf.header.num_sections = self.elf.sections.len().try_into().unwrap_or(0);
         // Update pseudo-section.
@@ -140,6 +221,60 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
         Ok(())
     }
 
+    /// Keep program header and section header at their old locations if the number of
+    /// segments and sections hasn't changed.
+    fn finish_in_place(&mut self) -> Result<bool, Error> {
+        let header = &self.elf.header;
+        if self.elf.segments.len() != header.num_segments as usize
+            || self.elf.sections.len() != header.num_sections as usize
+            || header.num_segments == u16::MAX
+            || (header.num_sections == 0 && !self.elf.sections.is_empty())
+        {
+            return Ok(false);
+        }
+        let (Some(section_header_range), Some(program_header_range)) = (
+            table_range(
+                header.section_header_offset,
+                self.elf.sections.len(),
+                header.class.section_len(),
+            ),
+            table_range(
+                header.program_header_offset,
+                self.elf.segments.len(),
+                header.class.segment_len(),
+            ),
+        ) else {
+            return Ok(false);
+        };
+        // The old section header and program header might have been overwritten by the new
+        // sections and segments. The program header is covered by `PHDR` and `LOAD` segments.
+        if self.elf.sections.iter().any(|section| {
+            section.kind != SectionKind::Null
+                && (ranges_overlap(&section.file_offset_range(), &section_header_range)
+                    || ranges_overlap(&section.file_offset_range(), &program_header_range))
+        }) || self.elf.segments.iter().any(|segment| {
+            segment.kind != SegmentKind::Null
+                && (ranges_overlap(&segment.file_offset_range(), &section_header_range)
+                    || (!matches!(
+                        segment.kind,
+                        SegmentKind::Loadable | SegmentKind::ProgramHeader
+                    ) && ranges_overlap(&segment.file_offset_range(), &program_header_range)))
+        }) {
+            return Ok(false);
+        }
+        // Keep the original order of the segments if it's valid.
+        if self
+            .elf
+            .segments
+            .check(&self.elf.header, self.elf.page_size())
+            .is_err()
+        {
+            self.elf.segments.finish();
+        }
+        log::trace!("Keeping program header and section header in place");
+        Ok(true)
+    }
+
     /// Get the interpreter.
     pub fn read_interpreter(&mut self) -> Result<Option<CString>, Error> {
         self.elf.read_interpreter(&mut self.file)
@@ -177,7 +312,36 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
     ///
     /// Adds or modifies `.interp` section and `INTERP` segment.
     pub fn set_interpreter(&mut self, interpreter: &CStr) -> Result<(), Error> {
+        if self.policy != PatchPolicy::Relocate {
+            if self.set_interpreter_in_place(interpreter)? {
+                return Ok(());
+            }
+            if self.policy == PatchPolicy::InPlaceOnly {
+                return Err(Error::DoesNotFit("Interpreter"));
+            }
+        }
         self.remove_interpreter()?;
+        if self.elf.sections.is_empty() {
+            // Don't add section header if the file didn't have one.
+            let len = (interpreter.count_bytes() + 1) as u64;
+            let i = self.alloc_segment(Segment {
+                kind: SegmentKind::Interpreter,
+                flags: SegmentFlags::READABLE,
+                virtual_address: 0,
+                physical_address: 0,
+                offset: 0,
+                file_size: len,
+                memory_size: len,
+                align: INTERP_ALIGN,
+            })?;
+            self.elf.segments[i].write_content(
+                &mut self.file,
+                self.elf.header.class,
+                self.elf.header.byte_order,
+                interpreter,
+            )?;
+            return Ok(());
+        }
         let name_offset = self.get_name_offset(INTERP_SECTION)?;
         // Add `.interp` section and overlay it with LOAD segment.
         let i = self.alloc_section(Section {
@@ -215,266 +379,2001 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
         Ok(())
     }
 
+    /// Overwrite the contents of `INTERP` segment if the new interpreter fits.
+    fn set_interpreter_in_place(&mut self, interpreter: &CStr) -> Result<bool, Error> {
+        let Some(segment) = self
+            .elf
+            .segments
+            .iter()
+            .find(|segment| segment.kind == SegmentKind::Interpreter)
+        else {
+            log::trace!("Couldn't find INTERP segment");
+            return Ok(false);
+        };
+        let bytes = interpreter.to_bytes_with_nul();
+        let len = bytes.len() as u64;
+        if len > segment.file_size {
+            log::trace!(
+                "Interpreter {:?} doesn't fit into {} bytes",
+                interpreter,
+                segment.file_size
+            );
+            return Ok(false);
+        }
+        self.file.seek(segment.offset)?;
+        self.file.write_bytes(bytes)?;
+        write_zeroes(&mut self.file, segment.file_size - len)?;
+        log::trace!("Updated interpreter in place");
+        Ok(true)
+    }
+
     /// Remove all entries for the specified dynamic tag from the dynamic table.
     pub fn remove_dynamic_tag(&mut self, tag: DynamicTag) -> Result<(), Error> {
-        let Some(i) = self
-            .elf
-            .sections
+        self.retain_dynamic_entries(|kind, _value| kind != tag)
+    }
+
+    /// Read dynamic table.
+    pub fn read_dynamic_table(&mut self) -> Result<Option<DynamicTable>, Error> {
+        self.elf.read_dynamic_table(&mut self.file)
+    }
+
+    /// Read dynamic string table.
+    pub fn read_dynamic_string_table(&mut self) -> Result<Option<StringTable>, Error> {
+        self.elf.read_dynamic_string_table(&mut self.file)
+    }
+
+    /// Set the value under the specified dynamic tag in the dynamic table.
+    ///
+    /// Does nothing if the table is not present in the file.
+    #[deprecated(note = "Use `set_dynamic_tag` instead.")]
+    pub fn set_library_search_path<'a>(
+        &mut self,
+        entry_kind: DynamicTag,
+        value: impl Into<DynamicValue<'a>>,
+    ) -> Result<(), Error> {
+        self.set_dynamic_tag(entry_kind, value)
+    }
+
+    /// Set the value under the specified dynamic tag in the dynamic table.
+    ///
+    /// Supports `RPATH`, `RUNPATH` and `SONAME`. Setting either `RPATH` or `RUNPATH` removes the
+    /// other one.
+    /// Does nothing if the table is not present in the file.
+    pub fn set_dynamic_tag<'a>(
+        &mut self,
+        entry_kind: DynamicTag,
+        value: impl Into<DynamicValue<'a>>,
+    ) -> Result<(), Error> {
+        use DynamicTag::*;
+        assert!(matches!(entry_kind, Rpath | Runpath | SharedObjectName));
+        let (string, word) = match value.into() {
+            DynamicValue::CStr(value) => (Some(value), 0),
+            DynamicValue::Word(value) => (None, value),
+        };
+        if let (Some(string), true) = (string, self.policy != PatchPolicy::Relocate) {
+            if self.set_dynamic_string_in_place(entry_kind, string)? {
+                return Ok(());
+            }
+            if self.policy == PatchPolicy::InPlaceOnly {
+                return Err(Error::DoesNotFit("Dynamic table string"));
+            }
+        }
+        self.update_dynamic_table(string.as_slice(), |dynamic_table, offsets| {
+            let value = offsets.first().copied().unwrap_or(word);
+            if matches!(entry_kind, Rpath | Runpath) {
+                dynamic_table.retain(|(kind, _value)| {
+                    let retain = !matches!(kind, Rpath | Runpath);
+                    if !retain {
+                        log::trace!("Removing dynamic table entry {:?}", kind);
+                    }
+                    retain
+                });
+            }
+            dynamic_table.set(entry_kind, value);
+        })
+    }
+
+    /// Overwrite the string of the existing dynamic table entry if the new string fits.
+    ///
+    /// The string is overwritten only if it's not shared with other entries or symbols.
+    fn set_dynamic_string_in_place(
+        &mut self,
+        entry_kind: DynamicTag,
+        string: &CStr,
+    ) -> Result<bool, Error> {
+        use DynamicTag::*;
+        let dynamic_section_index = match self.find_dynamic_table() {
+            Some(DynamicLocation::Section(i)) => i,
+            Some(DynamicLocation::Segment(_)) => {
+                // Without the sections we can't check that the string isn't shared.
+                log::trace!("Couldn't find DYNAMIC section");
+                return Ok(false);
+            }
+            None => {
+                log::trace!("Couldn't find DYNAMIC section");
+                // Nothing to patch.
+                return Ok(true);
+            }
+        };
+        let location = DynamicLocation::Section(dynamic_section_index);
+        let mut dynamic_table = self.read_dynamic_table_at(location)?;
+        let same_kind = |kind: DynamicTag| {
+            kind == entry_kind
+                || matches!(entry_kind, Rpath | Runpath) && matches!(kind, Rpath | Runpath)
+        };
+        let Some(entry_index) = dynamic_table
             .iter()
-            .position(|section| section.kind == SectionKind::Dynamic)
+            .position(|(kind, _)| *kind == entry_kind)
+            .or_else(|| dynamic_table.iter().position(|(kind, _)| same_kind(*kind)))
         else {
-            return Ok(());
+            log::trace!("Couldn't find dynamic table entry {:?}", entry_kind);
+            return Ok(false);
         };
-        let section = &self.elf.sections[i];
-        self.file.seek(section.offset)?;
-        let mut table = DynamicTable::read(
-            &mut self.file,
-            self.elf.header.class,
-            self.elf.header.byte_order,
-            section.size,
-        )?;
-        table.retain(|(kind, _value)| {
-            let retain = *kind != tag;
-            if !retain {
-                log::trace!("Removing dynamic table entry {:?}", tag);
+        let Some(dynstr_table_index) = self.find_dynamic_string_table(&dynamic_table)? else {
+            log::trace!("Couldn't find `.dynstr` section");
+            return Ok(false);
+        };
+        let dynstr_section = &self.elf.sections[dynstr_table_index];
+        let dynstr_table: StringTable = self
+            .elf
+            .read_section_content(dynstr_section, &mut self.file)?;
+        let bytes = dynstr_table.as_bytes();
+        let offset = dynamic_table[entry_index].1;
+        let Some(old_string) = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| dynstr_table.get_string(offset))
+        else {
+            return Ok(false);
+        };
+        let len = string.count_bytes() as u64;
+        let old_len = old_string.count_bytes() as u64;
+        if len > old_len {
+            log::trace!("{:?} doesn't fit in place of {:?}", string, old_string);
+            return Ok(false);
+        }
+        // Check that the string is not a suffix of another one...
+        if offset != 0 && bytes[offset as usize - 1] != 0 {
+            log::trace!("{:?} is shared with another string", old_string);
+            return Ok(false);
+        }
+        // ...and that nothing else points inside the string.
+        let string_range = offset..offset + old_len + 1;
+        let mut references: Vec<u64> = dynamic_table
+            .iter()
+            .enumerate()
+            .filter(|(i, (kind, _))| *i != entry_index && kind.is_string_offset())
+            .map(|(_, (_, value))| *value)
+            .collect();
+        for i in 0..self.elf.sections.len() {
+            let section = &self.elf.sections[i];
+            if !matches!(
+                section.kind,
+                SectionKind::SymbolTable | SectionKind::DynamicSymbolTable
+            ) || section.link as usize != dynstr_table_index
+            {
+                continue;
+            }
+            let symbols: SymbolTable = self.elf.read_section_content(section, &mut self.file)?;
+            references.extend(symbols.iter().map(|symbol| symbol.name_offset as u64));
+        }
+        for i in 0..self.elf.sections.len() {
+            let section = &self.elf.sections[i];
+            if matches!(
+                section.kind,
+                SectionKind::GNU_VERDEF | SectionKind::GNU_VERNEED
+            ) && section.link as usize == dynstr_table_index
+            {
+                self.read_version_string_offsets(i, &mut references)?;
             }
+        }
+        if references
+            .iter()
+            .any(|reference| string_range.contains(reference))
+        {
+            log::trace!("{:?} is referenced elsewhere", old_string);
+            return Ok(false);
+        }
+        let dynstr_section = &self.elf.sections[dynstr_table_index];
+        self.file.seek(dynstr_section.offset + offset)?;
+        self.file.write_bytes(string.to_bytes_with_nul())?;
+        write_zeroes(&mut self.file, old_len - len)?;
+        log::trace!("Replaced {:?} with {:?} in place", old_string, string);
+        // Setting either `RPATH` or `RUNPATH` removes the other one.
+        dynamic_table[entry_index].0 = entry_kind;
+        let mut i = 0;
+        dynamic_table.retain(|(kind, _)| {
+            let retain = i == entry_index || !same_kind(*kind);
+            i += 1;
             retain
         });
-        // Update DYNAMIC section.
-        let table_len = table.in_file_len(self.elf.header.class) as u64;
-        let section = &mut self.elf.sections[i];
-        section.size = table_len;
-        self.file.seek(section.offset)?;
-        table.write(
-            &mut self.file,
-            self.elf.header.class,
-            self.elf.header.byte_order,
-        )?;
-        // Update DYNAMIC segment.
-        let Some(i) = self
-            .elf
-            .segments
-            .iter()
-            .position(|segment| segment.kind == SegmentKind::Dynamic)
-        else {
+        self.write_dynamic_table(location, dynamic_table)?;
+        Ok(true)
+    }
+
+    /// Read `.dynstr` offsets from version definition or version requirement section.
+    fn read_version_string_offsets(
+        &mut self,
+        i: usize,
+        offsets: &mut Vec<u64>,
+    ) -> Result<(), Error> {
+        let section = &self.elf.sections[i];
+        let kind = section.kind;
+        let num_entries = section.info;
+        let byte_order = self.elf.header.byte_order;
+        let content: Vec<u8> = self.elf.read_section_content(section, &mut self.file)?;
+        let mut cursor = crate::Cursor::new(&content[..]);
+        let mut offset = 0_u64;
+        for _ in 0..num_entries {
+            cursor.seek(offset)?;
+            let (num_aux, aux, next) = if kind == SectionKind::GNU_VERNEED {
+                // vn_version, vn_cnt, vn_file, vn_aux, vn_next
+                let _version = cursor.read_u16(byte_order)?;
+                let num_aux = cursor.read_u16(byte_order)?;
+                offsets.push(cursor.read_u32(byte_order)?.into());
+                (
+                    num_aux,
+                    cursor.read_u32(byte_order)?,
+                    cursor.read_u32(byte_order)?,
+                )
+            } else {
+                // vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next
+                cursor.seek(offset + 6)?;
+                let num_aux = cursor.read_u16(byte_order)?;
+                let _hash = cursor.read_u32(byte_order)?;
+                (
+                    num_aux,
+                    cursor.read_u32(byte_order)?,
+                    cursor.read_u32(byte_order)?,
+                )
+            };
+            let mut aux_offset = offset + aux as u64;
+            for _ in 0..num_aux {
+                if kind == SectionKind::GNU_VERNEED {
+                    // vna_hash, vna_flags, vna_other, vna_name, vna_next
+                    cursor.seek(aux_offset + 8)?;
+                } else {
+                    // vda_name, vda_next
+                    cursor.seek(aux_offset)?;
+                }
+                offsets.push(cursor.read_u32(byte_order)?.into());
+                let next = cursor.read_u32(byte_order)?;
+                if next == 0 {
+                    break;
+                }
+                aux_offset += next as u64;
+            }
+            if next == 0 {
+                break;
+            }
+            offset += next as u64;
+        }
+        Ok(())
+    }
+
+    /// Add `NEEDED` entry with the specified library name to the dynamic table.
+    ///
+    /// The entry is inserted before the `position`-th `NEEDED` entry, or after the last one if
+    /// `position` is out of bounds. The order of the other entries is preserved.
+    /// Does nothing if the library is already in the table or the table is not present in the
+    /// file.
+    pub fn add_needed(&mut self, library: &CStr, position: usize) -> Result<(), Error> {
+        if self.find_needed(library)?.is_some() {
+            log::trace!("{:?} is already in the dynamic table", library);
+            return Ok(());
+        }
+        self.update_dynamic_table(&[library], |dynamic_table, offsets| {
+            let mut needed = dynamic_table
+                .iter()
+                .enumerate()
+                .filter(|(_, (kind, _))| *kind == DynamicTag::Needed)
+                .map(|(i, _)| i);
+            let i = match needed.nth(position) {
+                Some(i) => i,
+                None => dynamic_table
+                    .iter()
+                    .rposition(|(kind, _)| *kind == DynamicTag::Needed)
+                    .map(|i| i + 1)
+                    .unwrap_or(0),
+            };
+            log::trace!(
+                "Inserting dynamic table entry NEEDED {:?} at index {i}",
+                library
+            );
+            dynamic_table.insert(i, (DynamicTag::Needed, offsets[0]));
+        })
+    }
+
+    /// Remove `NEEDED` entry with the specified library name from the dynamic table.
+    ///
+    /// The order of the other entries is preserved.
+    /// Does nothing if there is no such entry.
+    pub fn remove_needed(&mut self, library: &CStr) -> Result<(), Error> {
+        let Some(offset) = self.find_needed(library)? else {
+            return Ok(());
+        };
+        self.retain_dynamic_entries(|kind, value| !(kind == DynamicTag::Needed && value == offset))
+    }
+
+    /// Replace `NEEDED` entry with the library name `old` with the library name `new`.
+    ///
+    /// The order of the entries is preserved.
+    /// Does nothing if there is no such entry.
+    pub fn replace_needed(&mut self, old: &CStr, new: &CStr) -> Result<(), Error> {
+        if old == new {
+            return Ok(());
+        }
+        let Some(old_offset) = self.find_needed(old)? else {
             return Ok(());
         };
+        if self.find_needed(new)?.is_some() {
+            // Avoid duplicate entries.
+            return self.remove_needed(old);
+        }
+        self.update_dynamic_table(&[new], |dynamic_table, offsets| {
+            for (kind, value) in dynamic_table.iter_mut() {
+                if *kind == DynamicTag::Needed && *value == old_offset {
+                    log::trace!(
+                        "Replacing dynamic table entry NEEDED {:?} with {:?}",
+                        old,
+                        new
+                    );
+                    *value = offsets[0];
+                }
+            }
+        })
+    }
+
+    /// Find the string table offset of the `NEEDED` entry with the specified library name.
+    fn find_needed(&mut self, library: &CStr) -> Result<Option<u64>, Error> {
+        let Some(dynamic_table) = self.read_dynamic_table()? else {
+            return Ok(None);
+        };
+        let Some(strings) = self.read_dynamic_string_table()? else {
+            return Ok(None);
+        };
+        Ok(dynamic_table.iter().find_map(|(kind, value)| {
+            (*kind == DynamicTag::Needed && strings.get_string(*value as usize) == Some(library))
+                .then_some(*value)
+        }))
+    }
+
+    /// Make the stack executable or non-executable.
+    ///
+    /// Updates the flags of `GNU_STACK` segment or adds the segment if it's missing.
+    /// Similar to `execstack -s` and `execstack -c`.
+    pub fn set_executable_stack(&mut self, executable: bool) -> Result<(), Error> {
+        let i = self.get_gnu_stack_segment()?;
         let segment = &mut self.elf.segments[i];
-        segment.file_size = table_len;
-        segment.memory_size = table_len;
+        segment.flags.set(SegmentFlags::EXECUTABLE, executable);
+        log::trace!("Setting stack flags to {:?}", segment.flags);
         Ok(())
     }
 
-    /// Read dynamic table.
-    pub fn read_dynamic_table(&mut self) -> Result<Option<DynamicTable>, Error> {
-        self.elf.read_dynamic_table(&mut self.file)
+    /// Set the requested stack size.
+    ///
+    /// Updates the memory size of `GNU_STACK` segment or adds the segment if it's missing.
+    /// Zero size means the default stack size.
+    pub fn set_stack_size(&mut self, size: u64) -> Result<(), Error> {
+        let i = self.get_gnu_stack_segment()?;
+        log::trace!("Setting stack size to {size}");
+        self.elf.segments[i].memory_size = size;
+        Ok(())
     }
 
-    /// Read dynamic string table.
-    pub fn read_dynamic_string_table(&mut self) -> Result<Option<StringTable>, Error> {
-        self.elf.read_dynamic_string_table(&mut self.file)
+    /// Set operating system ABI.
+    ///
+    /// Similar to FreeBSD's `brandelf -t`.
+    pub fn set_os_abi(&mut self, os_abi: OsAbi) -> Result<(), Error> {
+        log::trace!("Setting OS ABI to {os_abi:?}");
+        self.update_header(|header| header.os_abi = os_abi)
+    }
+
+    /// Set ABI version.
+    pub fn set_abi_version(&mut self, abi_version: u8) -> Result<(), Error> {
+        log::trace!("Setting ABI version to {abi_version}");
+        self.update_header(|header| header.abi_version = abi_version)
+    }
+
+    /// Set program entry point.
+    ///
+    /// The entry point has to be covered by a `LOAD` segment. Zero means no entry point.
+    pub fn set_entry_point(&mut self, entry_point: u64) -> Result<(), Error> {
+        self.elf.segments.check_entry_point(entry_point)?;
+        log::trace!("Setting entry point to {entry_point:#x}");
+        self.update_header(|header| header.entry_point = entry_point)
+    }
+
+    /// Set or clear architecture-specific `flags` in the header.
+    ///
+    /// Fails if the flags are for another architecture.
+    pub fn set_flags<T: MachineFlags>(&mut self, flags: T, value: bool) -> Result<(), Error> {
+        let machine = self.elf.header.machine;
+        if machine != T::MACHINE {
+            return Err(Error::MachineMismatch(T::MACHINE, machine));
+        }
+        let mut new_flags = T::from_bits_retain(self.elf.header.flags);
+        new_flags.set(flags, value);
+        let new_flags = new_flags.bits();
+        log::trace!("Setting header flags to {new_flags:#x}");
+        self.update_header(|header| header.flags = new_flags)
+    }
+
+    /// Modify the copy of the header and replace the original if the copy is valid.
+    fn update_header(&mut self, f: impl FnOnce(&mut Header)) -> Result<(), Error> {
+        let mut header = self.elf.header.clone();
+        f(&mut header);
+        header.check()?;
+        self.elf.header = header;
+        Ok(())
+    }
+
+    /// Find `GNU_STACK` segment or add a new one with non-executable stack.
+    fn get_gnu_stack_segment(&mut self) -> Result<usize, Error> {
+        if let Some(i) = self
+            .elf
+            .segments
+            .iter()
+            .position(|segment| segment.kind == SegmentKind::GNU_STACK)
+        {
+            return Ok(i);
+        }
+        self.alloc_segment(Segment {
+            kind: SegmentKind::GNU_STACK,
+            flags: SegmentFlags::READABLE | SegmentFlags::WRITABLE,
+            virtual_address: 0,
+            physical_address: 0,
+            offset: 0,
+            file_size: 0,
+            memory_size: 0,
+            align: GNU_STACK_ALIGN,
+        })
+    }
+
+    /// Rename dynamic symbols using the mapping from old to new names.
+    ///
+    /// Both defined (exported) and undefined (imported) symbols in `.dynsym` are renamed, so
+    /// applying the same mapping to all the libraries in a closure keeps them consistent.
+    /// New names are added to `.dynstr` and `.hash` and `.gnu.hash` tables are rebuilt.
+    /// GNU hash table requires the hashed symbols to be sorted by their bucket index, hence the
+    /// symbols might be reordered. In this case `.gnu.version` and the dynamic relocations are
+    /// updated accordingly.
+    ///
+    /// Does nothing if none of the symbols match.
+    pub fn rename_dynamic_symbols(
+        &mut self,
+        mapping: &BTreeMap<CString, CString>,
+    ) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        let Some(dynsym_index) = self
+            .elf
+            .sections
+            .iter()
+            .position(|section| section.kind == SectionKind::DynamicSymbolTable)
+        else {
+            log::trace!("Couldn't find DYNSYM section");
+            return Ok(());
+        };
+        let mut symbols: SymbolTable = self
+            .elf
+            .read_section_content(&self.elf.sections[dynsym_index], &mut self.file)?;
+        let old_dynstr_index = self.elf.sections[dynsym_index].link as usize;
+        let Some(dynstr_section) = self.elf.sections.get(old_dynstr_index) else {
+            log::trace!("Couldn't find `.dynstr` section");
+            return Ok(());
+        };
+        let strings: StringTable = self
+            .elf
+            .read_section_content(dynstr_section, &mut self.file)?;
+        let mut renamed = Vec::new();
+        let mut new_names = Vec::new();
+        for (i, symbol) in symbols.iter().enumerate() {
+            let Some(old_name) = strings.get_string(symbol.name_offset as usize) else {
+                continue;
+            };
+            if let Some(new_name) = mapping.get(old_name) {
+                log::trace!("Renaming dynamic symbol {old_name:?} to {new_name:?}");
+                renamed.push(i);
+                new_names.push(new_name.as_c_str());
+            }
+        }
+        if renamed.is_empty() {
+            return Ok(());
+        }
+        let mut offsets = Vec::new();
+        self.update_dynamic_table(&new_names, |_, new_offsets| {
+            offsets.extend_from_slice(new_offsets)
+        })?;
+        let Some(new_dynstr_index) = self
+            .find_dynamic_section()
+            .map(|i| self.elf.sections[i].link as usize)
+        else {
+            return Ok(());
+        };
+        if offsets.len() != renamed.len() {
+            // `.dynstr` wasn't found.
+            return Ok(());
+        }
+        if new_dynstr_index != old_dynstr_index {
+            // `.dynsym`, `.gnu.version_d` and `.gnu.version_r` use the same string table.
+            let link = new_dynstr_index
+                .try_into()
+                .map_err(|_| Error::TooBig("Section link"))?;
+            for section in self.elf.sections.iter_mut() {
+                if section.link as usize == old_dynstr_index {
+                    section.link = link;
+                }
+            }
+        }
+        for (i, offset) in renamed.into_iter().zip(offsets) {
+            symbols[i].name_offset = offset
+                .try_into()
+                .map_err(|_| Error::TooBig("Symbol name offset"))?;
+        }
+        let strings: StringTable = self
+            .elf
+            .read_section_content(&self.elf.sections[new_dynstr_index], &mut self.file)?;
+        let name = |symbol: &Symbol| -> &[u8] {
+            strings
+                .get_string(symbol.name_offset as usize)
+                .unwrap_or_default()
+                .to_bytes()
+        };
+        // Rebuild `.gnu.hash`.
+        if let Some(i) = self.find_linked_section(SectionKind::GNU_HASH, dynsym_index) {
+            let mut table: GnuHashTable = self
+                .elf
+                .read_section_content(&self.elf.sections[i], &mut self.file)?;
+            let offset = (table.symbol_offset as usize).min(symbols.len());
+            let mut order: Vec<(u32, usize)> = symbols[offset..]
+                .iter()
+                .enumerate()
+                .map(|(j, symbol)| (gnu_hash(name(symbol)), offset + j))
+                .collect();
+            order.sort_by_key(|(hash, _)| table.bucket(*hash));
+            if order.iter().enumerate().any(|(j, (_, k))| offset + j != *k) {
+                let permutation: Vec<usize> = order.iter().map(|(_, k)| *k).collect();
+                self.reorder_symbols(dynsym_index, &mut symbols, offset, &permutation)?;
+            }
+            let hashes: Vec<u32> = order.into_iter().map(|(hash, _)| hash).collect();
+            table.rebuild(class, &hashes);
+            self.elf.sections[i].write_content(&mut self.file, class, byte_order, &table)?;
+        }
+        // Rebuild `.hash`.
+        if let Some(i) = self.find_linked_section(SectionKind::Hash, dynsym_index) {
+            let table: HashTable = self
+                .elf
+                .read_section_content(&self.elf.sections[i], &mut self.file)?;
+            let num_buckets = table.buckets.len() as u32;
+            let table = HashTable::new(num_buckets, symbols.iter().map(name));
+            self.elf.sections[i].write_content(&mut self.file, class, byte_order, &table)?;
+        }
+        self.elf.sections[dynsym_index].write_content(&mut self.file, class, byte_order, &symbols)
+    }
+
+    /// Change the binding of all `.symtab` symbols with the specified name.
+    ///
+    /// Local symbols are moved before the non-local ones and the first non-local symbol index
+    /// (section's `info` field) is updated. Similar to `objcopy --localize-symbol`,
+    /// `--globalize-symbol` and `--weaken-symbol`.
+    /// The dynamic symbol table is not modified.
+    pub fn set_symbol_binding(&mut self, name: &CStr, binding: SymbolBinding) -> Result<(), Error> {
+        self.edit_symbols(name, |symbol| symbol.binding = binding)
+    }
+
+    /// Change the visibility of all `.symtab` symbols with the specified name.
+    ///
+    /// The dynamic symbol table is not modified.
+    pub fn set_symbol_visibility(
+        &mut self,
+        name: &CStr,
+        visibility: SymbolVisibility,
+    ) -> Result<(), Error> {
+        self.edit_symbols(name, |symbol| symbol.visibility = visibility)
+    }
+
+    /// Modify `.symtab` symbols with the specified name via `f`.
+    fn edit_symbols(&mut self, name: &CStr, f: impl Fn(&mut Symbol)) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        for i in 0..self.elf.sections.len() {
+            let section = &self.elf.sections[i];
+            if section.kind != SectionKind::SymbolTable {
+                continue;
+            }
+            let Some(strings_section) = self.elf.sections.get(section.link as usize) else {
+                continue;
+            };
+            let strings: StringTable = self
+                .elf
+                .read_section_content(strings_section, &mut self.file)?;
+            let mut symbols: SymbolTable =
+                self.elf.read_section_content(section, &mut self.file)?;
+            let mut changed = false;
+            // Skip the first symbol that is always undefined.
+            for symbol in symbols.iter_mut().skip(1) {
+                if strings.get_string(symbol.name_offset as usize) == Some(name) {
+                    log::trace!("Updating symbol {name:?} in section [{i}]");
+                    f(symbol);
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            // Local symbols should preceed the others.
+            let mut permutation: Vec<usize> = (0..symbols.len()).collect();
+            permutation.sort_by_key(|j| symbols[*j].binding != SymbolBinding::Local);
+            if permutation.iter().enumerate().any(|(j, k)| j != *k) {
+                self.reorder_symbols(i, &mut symbols, 0, &permutation)?;
+            }
+            let num_locals = symbols
+                .iter()
+                .take_while(|symbol| symbol.binding == SymbolBinding::Local)
+                .count();
+            let section = &mut self.elf.sections[i];
+            section.info = num_locals
+                .try_into()
+                .map_err(|_| Error::TooBig("No. of local symbols"))?;
+            section.write_content(&mut self.file, class, byte_order, &symbols)?;
+        }
+        Ok(())
+    }
+
+    /// Reorder the symbols of the symbol table `table_index` starting from `offset`.
+    ///
+    /// `permutation[j]` is the old index of the symbol that is moved to index `offset + j`.
+    /// Updates symbol versions, extended section indices, section groups and symbol indices in
+    /// the relocations.
+    fn reorder_symbols(
+        &mut self,
+        table_index: usize,
+        symbols: &mut SymbolTable,
+        offset: usize,
+        permutation: &[usize],
+    ) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        log::trace!("Reordering symbols in section [{table_index}]");
+        let mut old_symbols: Vec<Option<Symbol>> = symbols.drain(offset..).map(Some).collect();
+        for old_index in permutation.iter() {
+            if let Some(symbol) = old_symbols[old_index - offset].take() {
+                symbols.push(symbol);
+            }
+        }
+        let mut new_indices: Vec<u32> = (0..(offset + permutation.len()) as u32).collect();
+        for (j, old_index) in permutation.iter().enumerate() {
+            new_indices[*old_index] = (offset + j) as u32;
+        }
+        // Reorder the tables that have one entry per symbol.
+        for (kind, entry_len) in [
+            (SectionKind::GNU_VERSYM, VERSYM_LEN),
+            (SectionKind::SymbolTableIndex, SYMTAB_SHNDX_LEN),
+        ] {
+            let Some(i) = self.find_linked_section(kind, table_index) else {
+                continue;
+            };
+            let entries: Vec<u8> = self
+                .elf
+                .read_section_content(&self.elf.sections[i], &mut self.file)?;
+            let mut new_entries = entries.clone();
+            for (j, old_index) in permutation.iter().enumerate() {
+                let old = old_index * entry_len;
+                let new = (offset + j) * entry_len;
+                if let (Some(dst), Some(src)) = (
+                    new_entries.get_mut(new..new + entry_len),
+                    entries.get(old..old + entry_len),
+                ) {
+                    dst.copy_from_slice(src);
+                }
+            }
+            self.elf.sections[i].write_content(&mut self.file, class, byte_order, &new_entries)?;
+        }
+        // Update relocations.
+        let remap = |symbol: &mut u32| {
+            if let Some(new_index) = new_indices.get(*symbol as usize) {
+                *symbol = *new_index;
+            }
+        };
+        for section in self.elf.sections.iter() {
+            if section.link as usize != table_index {
+                continue;
+            }
+            match section.kind {
+                SectionKind::RelTable => {
+                    let mut table: RelTable =
+                        self.elf.read_section_content(section, &mut self.file)?;
+                    table.iter_mut().for_each(|rel| remap(&mut rel.symbol));
+                    section.write_content(&mut self.file, class, byte_order, &table)?;
+                }
+                SectionKind::RelaTable => {
+                    let mut table: RelaTable =
+                        self.elf.read_section_content(section, &mut self.file)?;
+                    table
+                        .iter_mut()
+                        .for_each(|rela| remap(&mut rela.rel.symbol));
+                    section.write_content(&mut self.file, class, byte_order, &table)?;
+                }
+                _ => {}
+            }
+        }
+        // Update section group signatures.
+        for section in self.elf.sections.iter_mut() {
+            if section.kind == SectionKind::Group && section.link as usize == table_index {
+                if let Some(new_index) = new_indices.get(section.info as usize) {
+                    section.info = *new_index;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the section of the specified kind that is linked to the section `link`.
+    fn find_linked_section(&self, kind: SectionKind, link: usize) -> Option<usize> {
+        self.elf
+            .sections
+            .iter()
+            .position(|section| section.kind == kind && section.link as usize == link)
+    }
+
+    /// Remove dynamic table entries that don't match the predicate.
+    fn retain_dynamic_entries(
+        &mut self,
+        mut f: impl FnMut(DynamicTag, u64) -> bool,
+    ) -> Result<(), Error> {
+        self.edit_dynamic_table(|table| {
+            table.retain(|(kind, value)| {
+                let retain = f(*kind, *value);
+                if !retain {
+                    log::trace!("Removing dynamic table entry {:?}", kind);
+                }
+                retain
+            });
+        })
+    }
+
+    /// Add `strings` to `.dynstr`, modify the dynamic table via `f` and write both tables back.
+    ///
+    /// `f` receives the offsets of the `strings` in the updated string table.
+    fn update_dynamic_table(
+        &mut self,
+        strings: &[&CStr],
+        f: impl FnOnce(&mut DynamicTable, &[u64]),
+    ) -> Result<(), Error> {
+        use DynamicTag::*;
+        let Some(location) = self.find_dynamic_table() else {
+            log::trace!("Couldn't find DYNAMIC section");
+            return Ok(());
+        };
+        let mut dynamic_table = self.read_dynamic_table_at(location)?;
+        let dynamic_section_index = match location {
+            DynamicLocation::Section(i) => i,
+            DynamicLocation::Segment(_) => {
+                let Some((offsets, address, size)) =
+                    self.add_dynamic_strings_without_sections(&dynamic_table, strings)?
+                else {
+                    return Ok(());
+                };
+                f(&mut dynamic_table, &offsets);
+                dynamic_table.set(StringTableAddress, address);
+                dynamic_table.set(StringTableSize, size);
+                return self.write_dynamic_table(location, dynamic_table);
+            }
+        };
+        // Update `.dynstr` table.
+        let dynstr_table_index = {
+            let dynstr_table_index = self.find_dynamic_string_table(&dynamic_table)?;
+            let Some(dynstr_table_index) = dynstr_table_index else {
+                log::trace!("Couldn't find `.dynstr` section");
+                return Ok(());
+            };
+            let mut dynstr_table: StringTable = self
+                .elf
+                .read_section_content(&self.elf.sections[dynstr_table_index], &mut self.file)?;
+            let old_len = dynstr_table.as_bytes().len();
+            let offsets: Vec<u64> = strings
+                .iter()
+                .map(|string| {
+                    let offset = dynstr_table.insert(string);
+                    log::trace!(
+                        "Adding string {string:?} to {DYNSTR_SECTION:?} at offset {offset}"
+                    );
+                    offset as u64
+                })
+                .collect();
+            let dynstr_table_index = if dynstr_table.as_bytes().len() != old_len {
+                self.realloc_string_table(dynstr_table_index, DYNSTR_SECTION, &dynstr_table)?
+            } else {
+                dynstr_table_index
+            };
+            // Write `.dynstr` section.
+            let dynstr_table_section = &self.elf.sections[dynstr_table_index];
+            dynstr_table_section.write_content(
+                &mut self.file,
+                self.elf.header.class,
+                self.elf.header.byte_order,
+                &dynstr_table,
+            )?;
+            // Update dynamic table.
+            f(&mut dynamic_table, &offsets);
+            dynamic_table.set(StringTableAddress, dynstr_table_section.virtual_address);
+            dynamic_table.set(StringTableSize, dynstr_table_section.size);
+            log::trace!("Updated `.dynstr` table");
+            dynstr_table_index
+        };
+        self.elf.sections[dynamic_section_index].link = dynstr_table_index
+            .try_into()
+            .map_err(|_| Error::TooBig("Section link"))?;
+        self.write_dynamic_table(location, dynamic_table)
+    }
+
+    /// Add `strings` to the dynamic string table that is found via `STRTAB` and `STRSZ` entries.
+    ///
+    /// Used when there is no `.dynamic` section. The old table is kept intact and the new one
+    /// is written to a new `LOAD` segment if the strings are not already in the table.
+    /// Returns the offsets of the `strings`, the address and the size of the new table.
+    fn add_dynamic_strings_without_sections(
+        &mut self,
+        dynamic_table: &DynamicTable,
+        strings: &[&CStr],
+    ) -> Result<Option<(Vec<u64>, u64, u64)>, Error> {
+        use DynamicTag::*;
+        let class = self.elf.header.class;
+        let (Some(address), Some(size)) = (
+            dynamic_table.get(StringTableAddress),
+            dynamic_table.get(StringTableSize),
+        ) else {
+            log::trace!("Couldn't find dynamic string table");
+            return Ok(None);
+        };
+        let Some(offset) = self.elf.address_to_offset(address, size) else {
+            log::trace!("Dynamic string table is not mapped from the file");
+            return Ok(None);
+        };
+        self.elf.limits().check_string_table_size(size)?;
+        self.file.seek(offset)?;
+        let mut table = StringTable::read(&mut self.file, size)?;
+        let offsets: Vec<u64> = strings
+            .iter()
+            .map(|string| {
+                let offset = table.insert(string);
+                log::trace!("Adding string {string:?} to dynamic string table at offset {offset}");
+                offset as u64
+            })
+            .collect();
+        let new_size = table.as_bytes().len() as u64;
+        if new_size == size {
+            return Ok(Some((offsets, address, size)));
+        }
+        let alloc = SpaceAllocator::new(
+            class,
+            self.elf.page_size(),
+            &self.elf.sections,
+            &mut self.elf.segments,
+        );
+        let (padding, i) = alloc.allocate_loadable_segment(
+            new_size,
+            new_size,
+            STRING_TABLE_ALIGN,
+            SegmentFlags::READABLE,
+        )?;
+        let segment = &self.elf.segments[i];
+        self.file.seek(segment.offset + padding)?;
+        table.write(&mut self.file)?;
+        Ok(Some((offsets, segment.virtual_address + padding, new_size)))
+    }
+
+    /// Find `.dynstr` section.
+    fn find_dynamic_string_table(
+        &mut self,
+        dynamic_table: &DynamicTable,
+    ) -> Result<Option<usize>, Error> {
+        let i = match dynamic_table.get(DynamicTag::StringTableAddress) {
+            Some(addr) => {
+                // Find string table by its virtual address.
+                self.elf.sections.iter().position(|section| {
+                    section.kind == SectionKind::StringTable && section.virtual_address == addr
+                })
+            }
+            None => {
+                // Couldn't find string table's address in the dynamic table.
+                // Try to find the string table by section name.
+                let names = get_section_names!(self);
+                self.elf.sections.iter().position(|section| {
+                    section.kind == SectionKind::StringTable
+                        && Some(DYNSTR_SECTION) == names.get_string(section.name_offset as usize)
+                })
+            }
+        };
+        Ok(i)
+    }
+
+    /// Modify the dynamic table via `f` and write it back.
+    ///
+    /// The table is updated in place if it fits into the `.dynamic` section (including the spare
+    /// `NULL` entries), otherwise the section is moved to a new location. In the latter case
+    /// the symbols that point to the table (i.e. `_DYNAMIC`), the first entry of the global
+    /// offset table and the relative relocations that target the table are updated as well.
+    ///
+    /// Does nothing if the table is not present in the file.
+    pub fn edit_dynamic_table(&mut self, f: impl FnOnce(&mut DynamicTable)) -> Result<(), Error> {
+        let Some(location) = self.find_dynamic_table() else {
+            log::trace!("Couldn't find DYNAMIC section");
+            return Ok(());
+        };
+        let mut dynamic_table = self.read_dynamic_table_at(location)?;
+        f(&mut dynamic_table);
+        self.write_dynamic_table(location, dynamic_table)
+    }
+
+    /// Set the value of the first entry with the specified tag and remove the other entries with
+    /// this tag.
+    ///
+    /// Adds new entry if there is no such tag in the table.
+    /// Does nothing if the table is not present in the file.
+    pub fn set_dynamic_entry(&mut self, tag: DynamicTag, value: u64) -> Result<(), Error> {
+        self.edit_dynamic_table(|table| table.set(tag, value))
+    }
+
+    /// Add new entry to the end of the dynamic table.
+    ///
+    /// Unlike [`set_dynamic_entry`](Self::set_dynamic_entry) keeps the existing entries with
+    /// the same tag.
+    /// Returns an error if the `tag` is [`NULL`](DynamicTag::Null).
+    /// Does nothing if the table is not present in the file.
+    pub fn add_dynamic_entry(&mut self, tag: DynamicTag, value: u64) -> Result<(), Error> {
+        self.insert_dynamic_entry(usize::MAX, tag, value)
+    }
+
+    /// Insert new entry at the specified `index` in the dynamic table.
+    ///
+    /// The entry is added to the end of the table if `index` is out of bounds.
+    /// The existing entries with the same tag are kept.
+    /// Returns an error if the `tag` is [`NULL`](DynamicTag::Null).
+    /// Does nothing if the table is not present in the file.
+    pub fn insert_dynamic_entry(
+        &mut self,
+        index: usize,
+        tag: DynamicTag,
+        value: u64,
+    ) -> Result<(), Error> {
+        if tag == DynamicTag::Null {
+            return Err(Error::InvalidDynamicEntryKind(tag.as_u32()));
+        }
+        self.edit_dynamic_table(|table| {
+            let i = index.min(table.len());
+            log::trace!("Inserting dynamic table entry {tag:?} = {value:#x} at index {i}");
+            table.insert(i, (tag, value));
+        })
+    }
+
+    fn find_dynamic_section(&self) -> Option<usize> {
+        self.elf
+            .sections
+            .iter()
+            .position(|section| section.kind == SectionKind::Dynamic)
+    }
+
+    /// Find `.dynamic` section or `DYNAMIC` segment if there is no such section.
+    fn find_dynamic_table(&self) -> Option<DynamicLocation> {
+        self.find_dynamic_section()
+            .map(DynamicLocation::Section)
+            .or_else(|| {
+                self.elf
+                    .segments
+                    .iter()
+                    .position(|segment| segment.kind == SegmentKind::Dynamic)
+                    .map(DynamicLocation::Segment)
+            })
+    }
+
+    /// Get in-file offset, size and virtual address of the dynamic table.
+    fn dynamic_table_block(&self, location: DynamicLocation) -> (u64, u64, u64) {
+        match location {
+            DynamicLocation::Section(i) => {
+                let section = &self.elf.sections[i];
+                (section.offset, section.size, section.virtual_address)
+            }
+            DynamicLocation::Segment(i) => {
+                let segment = &self.elf.segments[i];
+                (segment.offset, segment.file_size, segment.virtual_address)
+            }
+        }
+    }
+
+    fn read_dynamic_table_at(&mut self, location: DynamicLocation) -> Result<DynamicTable, Error> {
+        let (offset, size, _) = self.dynamic_table_block(location);
+        self.elf.limits().check_section_size(size)?;
+        self.file.seek(offset)?;
+        DynamicTable::read(
+            &mut self.file,
+            self.elf.header.class,
+            self.elf.header.byte_order,
+            size,
+        )
+    }
+
+    fn write_dynamic_table(
+        &mut self,
+        location: DynamicLocation,
+        dynamic_table: DynamicTable,
+    ) -> Result<(), Error> {
+        let dynamic_table_len = dynamic_table.in_file_len(self.elf.header.class) as u64;
+        let (offset, size, old_dynamic_table_virtual_address) = self.dynamic_table_block(location);
+        if dynamic_table_len <= size {
+            // Reuse the existing section, the remaining space is filled with NULL entries.
+            self.file.seek(offset)?;
+            dynamic_table.write(
+                &mut self.file,
+                self.elf.header.class,
+                self.elf.header.byte_order,
+            )?;
+            write_zeroes(&mut self.file, size - dynamic_table_len)?;
+            log::trace!("Updated DYNAMIC section in place");
+            return Ok(());
+        }
+        let new_dynamic_table_virtual_address = match location {
+            DynamicLocation::Section(i) => self.move_dynamic_section(i, &dynamic_table)?,
+            DynamicLocation::Segment(i) => self.move_dynamic_segment(i, &dynamic_table)?,
+        };
+        log::trace!(
+            "Changed memory offset of the DYNAMIC segment from {:#x} to {:#x}",
+            old_dynamic_table_virtual_address,
+            new_dynamic_table_virtual_address
+        );
+        // Update symbol tables.
+        for section in self.elf.sections.iter() {
+            if !matches!(
+                section.kind,
+                SectionKind::SymbolTable | SectionKind::DynamicSymbolTable
+            ) {
+                continue;
+            }
+            let mut symbol_table: SymbolTable =
+                self.elf.read_section_content(section, &mut self.file)?;
+            let mut changed = false;
+            for symbol in symbol_table.iter_mut() {
+                if symbol.address == old_dynamic_table_virtual_address {
+                    log::trace!(
+                        "Changed dynamic table address from {:#x} to {:#x} in {:?}",
+                        symbol.address,
+                        new_dynamic_table_virtual_address,
+                        section.kind
+                    );
+                    symbol.address = new_dynamic_table_virtual_address;
+                    changed = true;
+                }
+            }
+            if changed {
+                self.file.seek(section.offset)?;
+                symbol_table.write(
+                    &mut self.file,
+                    self.elf.header.class,
+                    self.elf.header.byte_order,
+                )?;
+            }
+        }
+        self.update_dynamic_table_references(
+            &dynamic_table,
+            old_dynamic_table_virtual_address,
+            new_dynamic_table_virtual_address,
+        )
+    }
+
+    /// Move `.dynamic` section and `DYNAMIC` segment to a new location.
+    ///
+    /// Returns the new virtual address of the table.
+    fn move_dynamic_section(
+        &mut self,
+        i: usize,
+        dynamic_table: &DynamicTable,
+    ) -> Result<u64, Error> {
+        let dynamic_table_len = dynamic_table.in_file_len(self.elf.header.class) as u64;
+        let old_section = self.free_section(i, DYNAMIC_SECTION)?;
+        let name_offset = self.get_name_offset(DYNAMIC_SECTION)?;
+        let dynamic_section_index = self.alloc_section(Section {
+            name_offset: name_offset
+                .try_into()
+                .map_err(|_| Error::TooBig("Section name"))?,
+            kind: SectionKind::Dynamic,
+            flags: SectionFlags::ALLOC | SectionFlags::WRITE,
+            virtual_address: 0,
+            offset: 0,
+            size: dynamic_table_len,
+            link: old_section.link,
+            info: 0,
+            align: DYNAMIC_ALIGN,
+            entry_len: self.elf.header.class.dynamic_len() as u64,
+        })?;
+        let section = &self.elf.sections[dynamic_section_index];
+        self.file.seek(section.offset)?;
+        dynamic_table.write(
+            &mut self.file,
+            self.elf.header.class,
+            self.elf.header.byte_order,
+        )?;
+        self.elf.segments.push(Segment {
+            kind: SegmentKind::Dynamic,
+            flags: SegmentFlags::READABLE | SegmentFlags::WRITABLE,
+            offset: section.offset,
+            virtual_address: section.virtual_address,
+            physical_address: section.virtual_address,
+            file_size: section.size,
+            memory_size: section.size,
+            align: section.align,
+        });
+        Ok(section.virtual_address)
+    }
+
+    /// Move `DYNAMIC` segment to a new `LOAD` segment without adding any sections.
+    ///
+    /// Returns the new virtual address of the table.
+    fn move_dynamic_segment(
+        &mut self,
+        i: usize,
+        dynamic_table: &DynamicTable,
+    ) -> Result<u64, Error> {
+        let dynamic_table_len = dynamic_table.in_file_len(self.elf.header.class) as u64;
+        self.free_segment(i)?;
+        let i = self.alloc_segment(Segment {
+            kind: SegmentKind::Dynamic,
+            flags: SegmentFlags::READABLE | SegmentFlags::WRITABLE,
+            offset: 0,
+            virtual_address: 0,
+            physical_address: 0,
+            file_size: dynamic_table_len,
+            memory_size: dynamic_table_len,
+            align: DYNAMIC_ALIGN,
+        })?;
+        let segment = &self.elf.segments[i];
+        self.file.seek(segment.offset)?;
+        dynamic_table.write(
+            &mut self.file,
+            self.elf.header.class,
+            self.elf.header.byte_order,
+        )?;
+        Ok(segment.virtual_address)
+    }
+
+    /// Update the words that hold the address of the moved dynamic table.
+    ///
+    /// These are the first entry of the global offset table and the relative relocations which
+    /// addend is the address of the dynamic table. Both are used to find `_DYNAMIC` at runtime.
+    fn update_dynamic_table_references(
+        &mut self,
+        dynamic_table: &DynamicTable,
+        old_address: u64,
+        new_address: u64,
+    ) -> Result<(), Error> {
+        use DynamicTag::*;
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        let word_len = class.word_len() as u64;
+        // The addresses of the words that might hold the address of the dynamic table.
+        let mut addresses: Vec<u64> = Vec::new();
+        // `_GLOBAL_OFFSET_TABLE_[0]`.
+        addresses.extend(dynamic_table.get(PltGot));
+        let names = get_section_names!(self);
+        for section in self.elf.sections.iter() {
+            if section.kind != SectionKind::ProgramBits {
+                continue;
+            }
+            let name = names.get_string(section.name_offset as usize);
+            if name == Some(GOT_SECTION) || name == Some(GOT_PLT_SECTION) {
+                addresses.push(section.virtual_address);
+            }
+        }
+        if let Some(relative) = self.elf.header.machine.relative_relocation_kind() {
+            // Relocations with explicit addends.
+            if let Some((offset, size)) =
+                self.find_dynamic_block(dynamic_table, RelaTableAddress, RelaTableSize)?
+            {
+                self.file.seek(offset)?;
+                let mut table = RelaTable::read(&mut self.file, class, byte_order, size)?;
+                let mut changed = false;
+                for rela in table.iter_mut() {
+                    if rela.rel.kind != relative
+                        || addend_to_address(rela.addend, class) != old_address
+                    {
+                        continue;
+                    }
+                    log::trace!(
+                        "Changed relative relocation addend at {:#x} from {:#x} to {:#x}",
+                        rela.rel.offset,
+                        old_address,
+                        new_address
+                    );
+                    rela.addend = address_to_addend(new_address, class);
+                    // The addend might be stored in place as well.
+                    addresses.push(rela.rel.offset);
+                    changed = true;
+                }
+                if changed {
+                    self.file.seek(offset)?;
+                    table.write(&mut self.file, class, byte_order)?;
+                }
+            }
+            // Relocations with implicit addends.
+            if let Some((offset, size)) =
+                self.find_dynamic_block(dynamic_table, RelTableAddress, RelTableSize)?
+            {
+                self.file.seek(offset)?;
+                let table = RelTable::read(&mut self.file, class, byte_order, size)?;
+                addresses.extend(
+                    table
+                        .iter()
+                        .filter(|rel| rel.kind == relative)
+                        .map(|rel| rel.offset),
+                );
+            }
+        }
+        // Packed relative relocations with implicit addends.
+        if let Some((offset, size)) =
+            self.find_dynamic_block(dynamic_table, RelrTableAddress, RelrTableSize)?
+        {
+            self.file.seek(offset)?;
+            let mut entries = Vec::with_capacity((size / word_len) as usize);
+            for _ in 0..size / word_len {
+                entries.push(self.file.read_word(class, byte_order)?);
+            }
+            decode_relr(&entries, word_len, &mut addresses);
+        }
+        addresses.sort_unstable();
+        addresses.dedup();
+        for address in addresses.into_iter() {
+            let Some(offset) = self.elf.address_to_offset(address, word_len) else {
+                continue;
+            };
+            self.file.seek(offset)?;
+            if self.file.read_word(class, byte_order)? != old_address {
+                continue;
+            }
+            self.file.seek(offset)?;
+            self.file.write_word(class, byte_order, new_address)?;
+            log::trace!(
+                "Changed dynamic table address from {:#x} to {:#x} at {:#x}",
+                old_address,
+                new_address,
+                address
+            );
+        }
+        Ok(())
+    }
+
+    /// Find in-file offset and size of the block which address and size are stored in the dynamic
+    /// table.
+    ///
+    /// The size is checked against the section size limit.
+    fn find_dynamic_block(
+        &self,
+        dynamic_table: &DynamicTable,
+        address_tag: DynamicTag,
+        size_tag: DynamicTag,
+    ) -> Result<Option<(u64, u64)>, Error> {
+        let (Some(address), Some(size)) =
+            (dynamic_table.get(address_tag), dynamic_table.get(size_tag))
+        else {
+            return Ok(None);
+        };
+        let Some(offset) = self.elf.address_to_offset(address, size) else {
+            return Ok(None);
+        };
+        self.elf.limits().check_section_size(size)?;
+        Ok(Some((offset, size)))
+    }
+
+    /// Add new section with the specified name, type, flags, alignment and content.
+    ///
+    /// `ALLOC` sections are placed into a new or an existing `LOAD` segment,
+    /// other sections are placed into free space in the file.
+    /// The alignment should be zero or a power of two; zero and one mean no alignment.
+    /// `NULL` sections and `NOBITS` sections with non-empty `content` are rejected, use
+    /// [`add_nobits_section`](Self::add_nobits_section) for the latter.
+    /// Returns the index of the new section.
+    pub fn add_section(
+        &mut self,
+        name: &CStr,
+        kind: SectionKind,
+        flags: SectionFlags,
+        align: u64,
+        content: &[u8],
+    ) -> Result<usize, Error> {
+        if kind == SectionKind::Null || (kind == SectionKind::NoBits && !content.is_empty()) {
+            return Err(Error::InvalidSectionKind(kind.as_u32()));
+        }
+        let i = self.do_add_section(name, kind, flags, align, content.len() as u64)?;
+        if kind == SectionKind::NoBits {
+            return Ok(i);
+        }
+        self.elf.sections[i].write_content(
+            &mut self.file,
+            self.elf.header.class,
+            self.elf.header.byte_order,
+            &content,
+        )?;
+        Ok(i)
+    }
+
+    /// Add new `NOBITS` section with the specified name, flags, alignment and size.
+    ///
+    /// `ALLOC` sections occupy `size` bytes in memory but no space in the file,
+    /// e.g. they are zero-initialized like `.bss`.
+    /// Returns the index of the new section.
+    pub fn add_nobits_section(
+        &mut self,
+        name: &CStr,
+        flags: SectionFlags,
+        align: u64,
+        size: u64,
+    ) -> Result<usize, Error> {
+        self.do_add_section(name, SectionKind::NoBits, flags, align, size)
+    }
+
+    fn do_add_section(
+        &mut self,
+        name: &CStr,
+        kind: SectionKind,
+        flags: SectionFlags,
+        align: u64,
+        size: u64,
+    ) -> Result<usize, Error> {
+        if !align_is_valid(align) {
+            return Err(Error::InvalidAlign(align));
+        }
+        let name_offset = self.get_name_offset(name)?;
+        self.alloc_section(Section {
+            name_offset: name_offset
+                .try_into()
+                .map_err(|_| Error::TooBig("Section name offset"))?,
+            kind,
+            flags,
+            virtual_address: 0,
+            offset: 0,
+            size,
+            link: 0,
+            info: 0,
+            align,
+            entry_len: 0,
+        })
+    }
+
+    /// Remove all sections with the specified name.
+    ///
+    /// The contents of the sections are zeroed out and the space they occupied can be reused by
+    /// the sections that are added later. The indices of the remaining sections are updated in
+    /// the section header, in the section groups, in the symbol tables and in the ELF header.
+    ///
+    /// Refuses to remove a section that is referenced by other sections or by the ELF header.
+    /// `ALLOC` sections are also checked for references from the segments, the dynamic table,
+    /// the entry point and the symbols. The symbols defined in other sections are removed
+    /// unless they are used by the relocations or the section groups.
+    /// Does nothing if there is no such section.
+    pub fn remove_section(&mut self, name: &CStr) -> Result<(), Error> {
+        let names = get_section_names!(self);
+        let indices: Vec<usize> = (1..self.elf.sections.len())
+            .filter(|i| names.get_string(self.elf.sections[*i].name_offset as usize) == Some(name))
+            .collect();
+        self.remove_sections(indices)
+    }
+
+    /// Remove debug information.
+    ///
+    /// Removes `.debug_*` and `.zdebug_*` sections and the relocation sections that apply to them.
+    pub fn strip_debug(&mut self) -> Result<(), Error> {
+        let names = get_section_names!(self);
+        let mut indices: Vec<usize> = (1..self.elf.sections.len())
+            .filter(|i| {
+                let section = &self.elf.sections[*i];
+                !section.flags.contains(SectionFlags::ALLOC)
+                    && names
+                        .get_string(section.name_offset as usize)
+                        .is_some_and(is_debug_section_name)
+            })
+            .collect();
+        self.add_relocation_sections(&mut indices);
+        self.remove_sections(indices)
+    }
+
+    /// Remove debug information and the symbol table.
+    ///
+    /// In addition to what [`strip_debug`](Self::strip_debug) removes, this method removes
+    /// `.symtab`, the string table that it uses and the relocation sections that refer to it.
+    /// Dynamic symbols are preserved.
+    pub fn strip_all(&mut self) -> Result<(), Error> {
+        self.strip_debug()?;
+        let mut indices: Vec<usize> = (1..self.elf.sections.len())
+            .filter(|i| self.elf.sections[*i].kind == SectionKind::SymbolTable)
+            .collect();
+        let section_names_index = self.elf.header.section_names_index as usize;
+        for i in 0..indices.len() {
+            let link = self.elf.sections[indices[i]].link as usize;
+            // The string table can be shared with the section names.
+            if link != section_names_index
+                && self
+                    .elf
+                    .sections
+                    .get(link)
+                    .is_some_and(|section| section.kind == SectionKind::StringTable)
+            {
+                indices.push(link);
+            }
+        }
+        self.add_relocation_sections(&mut indices);
+        self.remove_sections(indices)
+    }
+
+    /// Get the link to the debug information file.
+    pub fn read_debuglink(&mut self) -> Result<Option<DebugLink>, Error> {
+        self.elf.read_debuglink(&mut self.file)
+    }
+
+    /// Link the file to the specified debug information file.
+    ///
+    /// Computes the checksum of the file the same way GDB does and adds or replaces
+    /// `.gnu_debuglink` section. Similar to `objcopy --add-gnu-debuglink`.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn set_debuglink<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), Error> {
+        let debuglink = DebugLink::from_file(path)?;
+        self.replace_debuglink(&debuglink)
+    }
+
+    /// Add or replace `.gnu_debuglink` section with the specified link.
+    pub fn replace_debuglink(&mut self, debuglink: &DebugLink) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        self.remove_section(GNU_DEBUGLINK_SECTION)?;
+        let mut content = Vec::new();
+        debuglink.write(&mut content, class, byte_order)?;
+        let name_offset = self.get_name_offset(GNU_DEBUGLINK_SECTION)?;
+        let i = self.alloc_section(Section {
+            name_offset: name_offset
+                .try_into()
+                .map_err(|_| Error::TooBig("Section name offset"))?,
+            kind: SectionKind::ProgramBits,
+            flags: SectionFlags::empty(),
+            virtual_address: 0,
+            offset: 0,
+            size: content.len() as u64,
+            link: 0,
+            info: 0,
+            align: GNU_DEBUGLINK_ALIGN,
+            entry_len: 0,
+        })?;
+        log::trace!(
+            "Setting debug link to {:?} with CRC {:#010x}",
+            debuglink.file_name,
+            debuglink.crc
+        );
+        self.elf.sections[i].write_content(&mut self.file, class, byte_order, debuglink)?;
+        Ok(())
+    }
+
+    /// Write debug information file to `writer`.
+    ///
+    /// The file has the same section layout as the original file, however, the contents of
+    /// `ALLOC` sections except notes are replaced with `NOBITS` placeholders.
+    /// This is what `objcopy --only-keep-debug` does.
+    /// The patched file is not modified, i.e. the debug file should be written before stripping.
+    pub fn write_debug_file<W: ElfWrite + ElfSeek>(&mut self, writer: &mut W) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let mut header = self.elf.header.clone();
+        let mut segments = self.elf.segments.clone();
+        let mut sections = self.elf.sections.clone();
+        header.program_header_offset = class.header_len() as u64;
+        let program_header_len = (segments.len() as u64)
+            .checked_mul(class.segment_len() as u64)
+            .ok_or(Error::TooBig("No. of segments"))?;
+        let mut offset = header.program_header_offset + program_header_len;
+        for segment in segments.iter_mut() {
+            segment.file_size = 0;
+        }
+        let mut content = Vec::new();
+        for section in sections.iter_mut().skip(1) {
+            if section.flags.contains(SectionFlags::ALLOC) && section.kind != SectionKind::Note
+                || section.kind == SectionKind::NoBits
+            {
+                section.kind = SectionKind::NoBits;
+                section.offset = offset;
+                continue;
+            }
+            self.elf.limits().check_section_size(section.size)?;
+            let new_offset = align_up(offset, section.align.max(1));
+            writer.seek(offset)?;
+            write_zeroes(writer, new_offset - offset)?;
+            // The size comes from the file and can't be trusted, hence copying in chunks.
+            self.file.seek(section.offset)?;
+            let mut remaining = section.size;
+            while remaining != 0 {
+                let chunk_len = remaining.min(READ_CHUNK_LEN as u64);
+                content.resize(chunk_len as usize, 0_u8);
+                self.file.read_bytes(&mut content[..])?;
+                writer.write_bytes(&content)?;
+                remaining -= chunk_len;
+            }
+            section.offset = new_offset;
+            offset = new_offset
+                .checked_add(section.size)
+                .ok_or(Error::TooBig("Section size"))?;
+        }
+        header.section_header_offset = align_up(offset, SECTION_HEADER_ALIGN);
+        writer.seek(offset)?;
+        write_zeroes(writer, header.section_header_offset - offset)?;
+        Elf::new(header, segments, sections, self.page_size).write(writer)
+    }
+
+    /// Reclaim the space freed by the previous modifications.
+    ///
+    /// Removes `LOAD` segments that no longer cover any section, segment or the entry point, and
+    /// moves non-`ALLOC` sections to the free space closest to the start of the file.
+    /// Virtual addresses are not changed. Program header and section header are moved by
+    /// [`finish`](Self::finish). Use [`finish_and_truncate`](Self::finish_and_truncate) to
+    /// remove the free space at the end of the file.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        self.remove_unused_segments()?;
+        self.move_non_alloc_sections()?;
+        Ok(())
+    }
+
+    fn remove_unused_segments(&mut self) -> Result<(), Error> {
+        if self
+            .elf
+            .sections
+            .iter()
+            .all(|section| section.kind == SectionKind::Null)
+        {
+            // Without sections we can't tell which parts of the segments are in use.
+            return Ok(());
+        }
+        let mut i = 0;
+        while i < self.elf.segments.len() {
+            if self.elf.segments[i].kind == SegmentKind::Loadable && !self.segment_is_used(i) {
+                self.free_segment(i)?;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `LOAD` segment `i` covers anything that is used.
+    fn segment_is_used(&self, i: usize) -> bool {
+        let segment = &self.elf.segments[i];
+        let file_range = segment.file_offset_range();
+        let address_range = segment.virtual_address_range();
+        if file_range.start == 0 && !file_range.is_empty() {
+            // ELF header.
+            return true;
+        }
+        if address_range.contains(&self.elf.header.entry_point) {
+            return true;
+        }
+        let overlaps = |range: core::ops::Range<u64>, other: &core::ops::Range<u64>| {
+            if range.is_empty() {
+                other.contains(&range.start)
+            } else {
+                range.start < other.end && other.start < range.end
+            }
+        };
+        if self.elf.sections.iter().any(|section| {
+            section.kind != SectionKind::Null
+                && (section.flags.contains(SectionFlags::ALLOC)
+                    && overlaps(section.virtual_address_range(), &address_range)
+                    || section.kind != SectionKind::NoBits
+                        && section.size != 0
+                        && overlaps(section.file_offset_range(), &file_range))
+        }) {
+            return true;
+        }
+        self.elf.segments.iter().enumerate().any(|(j, other)| {
+            j != i
+                && !matches!(other.kind, SegmentKind::Null | SegmentKind::Loadable)
+                && other.memory_size != 0
+                && overlaps(other.virtual_address_range(), &address_range)
+        })
+    }
+
+    fn move_non_alloc_sections(&mut self) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let mut indices: Vec<usize> = (1..self.elf.sections.len())
+            .filter(|i| {
+                let section = &self.elf.sections[*i];
+                !matches!(section.kind, SectionKind::Null | SectionKind::NoBits)
+                    && !section.flags.contains(SectionFlags::ALLOC)
+                    && section.size != 0
+            })
+            .collect();
+        indices.sort_unstable_by_key(|i| self.elf.sections[*i].offset);
+        for i in indices.into_iter() {
+            let content: Vec<u8> = self
+                .elf
+                .read_section_content(&self.elf.sections[i], &mut self.file)?;
+            // Exclude the section itself from the allocation.
+            let mut section = core::mem::take(&mut self.elf.sections[i]);
+            let alloc = SpaceAllocator::new(
+                class,
+                self.page_size,
+                &self.elf.sections,
+                &mut self.elf.segments,
+            );
+            match alloc.allocate_file_space(section.size, section.align) {
+                Some(offset) if offset < section.offset => {
+                    log::trace!(
+                        "Moving section [{i}], file offsets {:#x}..{:#x} -> {:#x}..{:#x}",
+                        section.offset,
+                        section.offset + section.size,
+                        offset,
+                        offset + section.size
+                    );
+                    section.clear_content(&mut self.file)?;
+                    section.offset = offset;
+                    self.file.seek(offset)?;
+                    self.file.write_bytes(&content)?;
+                }
+                _ => {}
+            }
+            self.elf.sections[i] = section;
+        }
+        Ok(())
+    }
+
+    /// Returns the offset of the first byte after the last byte used by the ELF.
+    fn file_end(&self) -> u64 {
+        let header = &self.elf.header;
+        let mut end = header.class.header_len() as u64;
+        end = end.max(
+            header.program_header_offset
+                + self.elf.segments.len() as u64 * header.class.segment_len() as u64,
+        );
+        if header.num_sections != 0 || self.elf.sections.len() > 1 {
+            end = end.max(
+                header.section_header_offset
+                    + self.elf.sections.len() as u64 * header.class.section_len() as u64,
+            );
+        }
+        for section in self.elf.sections.iter() {
+            if section.kind != SectionKind::Null {
+                end = end.max(section.file_offset_range().end);
+            }
+        }
+        for segment in self.elf.segments.iter() {
+            if segment.kind != SegmentKind::Null {
+                end = end.max(segment.file_offset_range().end);
+            }
+        }
+        end
+    }
+
+    /// Add relocation sections that apply to or use the sections with the specified indices.
+    fn add_relocation_sections(&self, indices: &mut Vec<usize>) {
+        for (i, section) in self.elf.sections.iter().enumerate() {
+            if matches!(section.kind, SectionKind::RelTable | SectionKind::RelaTable)
+                && !section.flags.contains(SectionFlags::ALLOC)
+                && (indices.contains(&(section.info as usize))
+                    || indices.contains(&(section.link as usize)))
+                && !indices.contains(&i)
+            {
+                indices.push(i);
+            }
+        }
+    }
+
+    /// Remove sections with the specified indices.
+    ///
+    /// Section groups that become empty are removed as well.
+    fn remove_sections(&mut self, mut indices: Vec<usize>) -> Result<(), Error> {
+        self.add_empty_groups(&mut indices)?;
+        indices.sort_unstable();
+        indices.dedup();
+        for i in indices.iter() {
+            self.check_section_references(*i, &indices)?;
+        }
+        // Remove in reverse order to keep the indices valid.
+        for i in indices.into_iter().rev() {
+            let names = get_section_names!(self);
+            let name: CString = names
+                .get_string(self.elf.sections[i].name_offset as usize)
+                .unwrap_or_default()
+                .into();
+            if self.elf.sections[i].kind == SectionKind::Group {
+                // The remaining members no longer belong to any group.
+                for member in self.read_u32_table(i)?.into_iter().skip(1) {
+                    if let Some(section) = self.elf.sections.get_mut(member as usize) {
+                        section.flags.remove(SectionFlags::GROUP);
+                    }
+                }
+            }
+            self.free_section(i, &name)?;
+            self.elf.sections.remove(i);
+            self.renumber_sections(i)?;
+        }
+        Ok(())
     }
 
-    /// Set the value under the specified dynamic tag in the dynamic table.
-    ///
-    /// Does nothing if the table is not present in the file.
-    #[deprecated(note = "Use `set_dynamic_tag` instead.")]
-    pub fn set_library_search_path<'a>(
-        &mut self,
-        entry_kind: DynamicTag,
-        value: impl Into<DynamicValue<'a>>,
-    ) -> Result<(), Error> {
-        self.set_dynamic_tag(entry_kind, value)
+    /// Add section groups all members of which are in `indices`.
+    fn add_empty_groups(&mut self, indices: &mut Vec<usize>) -> Result<(), Error> {
+        for i in 0..self.elf.sections.len() {
+            if self.elf.sections[i].kind != SectionKind::Group || indices.contains(&i) {
+                continue;
+            }
+            // The first entry contains the flags, the rest are the members.
+            let entries = self.read_u32_table(i)?;
+            if entries.len() > 1
+                && entries[1..]
+                    .iter()
+                    .all(|member| indices.contains(&(*member as usize)))
+            {
+                indices.push(i);
+            }
+        }
+        Ok(())
     }
 
-    /// Set the value under the specified dynamic tag in the dynamic table.
+    /// Check that section `i` isn't referenced by anything except the sections in `removed`.
     ///
-    /// Supports `RPATH`, `RUNPATH` and `SONAME`.
-    /// Does nothing if the table is not present in the file.
-    pub fn set_dynamic_tag<'a>(
-        &mut self,
-        entry_kind: DynamicTag,
-        value: impl Into<DynamicValue<'a>>,
-    ) -> Result<(), Error> {
-        use DynamicTag::*;
-        assert!(matches!(entry_kind, Rpath | Runpath | SharedObjectName));
-        // Read and remove dynamic table.
-        let (mut dynamic_table, old_dynamic_table_virtual_address) = match self
-            .elf
-            .sections
-            .iter()
-            .position(|section| section.kind == SectionKind::Dynamic)
-        {
-            Some(i) => {
-                let section = &self.elf.sections[i];
-                let virtual_address = section.virtual_address;
-                self.file.seek(section.offset)?;
-                let dynamic_table = DynamicTable::read(
-                    &mut self.file,
-                    self.elf.header.class,
-                    self.elf.header.byte_order,
-                    section.size,
-                )?;
-                self.free_section(i, DYNAMIC_SECTION)?;
-                (dynamic_table, virtual_address)
+    /// The symbols that are defined in a non-`ALLOC` section are removed together with the
+    /// section unless they are used by the relocations or the section groups.
+    fn check_section_references(&mut self, i: usize, removed: &[usize]) -> Result<(), Error> {
+        if i == self.elf.header.section_names_index as usize {
+            return Err(Error::SectionInUse(i, "ELF header"));
+        }
+        for (j, other) in self.elf.sections.iter().enumerate() {
+            if j == i || removed.contains(&j) {
+                continue;
             }
-            None => {
-                log::trace!("Couldn't find DYNAMIC section");
-                return Ok(());
+            if other.link as usize == i {
+                return Err(Error::SectionInUse(i, "section link"));
             }
-        };
-        // Update `.dynstr` table.
-        let dynstr_table_index = {
-            let dynstr_table_index = match dynamic_table.get(StringTableAddress) {
-                Some(addr) => {
-                    // Find string table by its virtual address.
-                    self.elf.sections.iter().position(|section| {
-                        section.kind == SectionKind::StringTable && section.virtual_address == addr
-                    })
-                }
-                None => {
-                    // Couldn't find string table's address in the dynamic table.
-                    // Try to find the string table by section name.
-                    let names = get_section_names!(self);
-                    self.elf.sections.iter().position(|section| {
-                        section.kind == SectionKind::StringTable
-                            && Some(DYNSTR_SECTION)
-                                == names.get_string(section.name_offset as usize)
-                    })
-                }
-            };
-            let Some(dynstr_table_index) = dynstr_table_index else {
-                log::trace!("Couldn't find `.dynstr` section");
-                return Ok(());
-            };
-            let mut dynstr_table: StringTable = self.elf.sections[dynstr_table_index]
-                .read_content(
-                    &mut self.file,
-                    self.elf.header.class,
-                    self.elf.header.byte_order,
-                )?;
-            let (value, dynstr_table_index) = match value.into() {
-                DynamicValue::CStr(value) => {
-                    let (offset, i) = self.get_string_offset(
-                        value,
-                        Some(dynstr_table_index),
-                        DYNSTR_SECTION,
-                        &mut dynstr_table,
-                    )?;
-                    (offset as u64, i)
+            if info_is_section_index(other) && other.info as usize == i {
+                return Err(Error::SectionInUse(i, "section info"));
+            }
+        }
+        let section = &self.elf.sections[i];
+        let alloc = section.flags.contains(SectionFlags::ALLOC);
+        if alloc {
+            self.check_address_references(i)?;
+        }
+        for j in 0..self.elf.sections.len() {
+            let symbol_table_section = &self.elf.sections[j];
+            if !matches!(
+                symbol_table_section.kind,
+                SectionKind::SymbolTable | SectionKind::DynamicSymbolTable
+            ) || removed.contains(&j)
+            {
+                continue;
+            }
+            let dynamic = symbol_table_section.kind == SectionKind::DynamicSymbolTable;
+            let symbol_table: SymbolTable = self
+                .elf
+                .read_section_content(symbol_table_section, &mut self.file)?;
+            let section_indices = self.read_symbol_section_indices(j, &symbol_table)?;
+            let defined: Vec<u32> = (0..symbol_table.len() as u32)
+                .filter(|k| section_indices[*k as usize] == i)
+                .collect();
+            if defined.is_empty() {
+                continue;
+            }
+            if alloc || dynamic {
+                return Err(Error::SectionInUse(i, "symbol"));
+            }
+            for (k, other) in self.elf.sections.iter().enumerate() {
+                if other.link as usize != j || removed.contains(&k) {
+                    continue;
                 }
-                DynamicValue::Word(value) => (value, dynstr_table_index),
-            };
-            // Write `.dynstr` section.
-            let dynstr_table_section = &self.elf.sections[dynstr_table_index];
-            dynstr_table_section.write_content(
-                &mut self.file,
-                self.elf.header.class,
-                self.elf.header.byte_order,
-                &dynstr_table,
-            )?;
-            // Update dynamic table.
-            dynamic_table.retain(|(kind, _value)| {
-                let retain = !matches!(kind, Rpath | Runpath);
-                if !retain {
-                    log::trace!("Removing dynamic table entry {:?}", kind);
+                let in_use = match other.kind {
+                    SectionKind::RelTable => {
+                        let table: RelTable =
+                            self.elf.read_section_content(other, &mut self.file)?;
+                        table.iter().any(|rel| defined.contains(&rel.symbol))
+                    }
+                    SectionKind::RelaTable => {
+                        let table: RelaTable =
+                            self.elf.read_section_content(other, &mut self.file)?;
+                        table.iter().any(|rela| defined.contains(&rela.rel.symbol))
+                    }
+                    SectionKind::Group => defined.contains(&other.info),
+                    _ => false,
+                };
+                if in_use {
+                    return Err(Error::SectionInUse(i, "symbol"));
                 }
-                retain
-            });
-            dynamic_table.set(StringTableAddress, dynstr_table_section.virtual_address);
-            dynamic_table.set(StringTableSize, dynstr_table_section.size);
-            dynamic_table.set(entry_kind, value);
-            log::trace!("Updated `.dynstr` table");
-            dynstr_table_index
-        };
-        // Update dynamic table.
-        let new_dynamic_table_virtual_address = {
-            let dynamic_table_len = dynamic_table.in_file_len(self.elf.header.class) as u64;
-            let name_offset = self.get_name_offset(DYNAMIC_SECTION)?;
-            let dynamic_section_index = self.alloc_section(Section {
-                name_offset: name_offset
-                    .try_into()
-                    .map_err(|_| Error::TooBig("Section name"))?,
-                kind: SectionKind::Dynamic,
-                flags: SectionFlags::ALLOC | SectionFlags::WRITE,
-                virtual_address: 0,
-                offset: 0,
-                size: dynamic_table_len,
-                link: dynstr_table_index
-                    .try_into()
-                    .map_err(|_| Error::TooBig("Section link"))?,
-                info: 0,
-                align: DYNAMIC_ALIGN,
-                entry_len: DYNAMIC_ENTRY_LEN,
-            })?;
-            let new_dynamic_table_virtual_address =
-                self.elf.sections[dynamic_section_index].virtual_address;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the addresses of the `ALLOC` section `i` aren't referenced by the entry point,
+    /// the segments or the dynamic table.
+    fn check_address_references(&mut self, i: usize) -> Result<(), Error> {
+        let section = &self.elf.sections[i];
+        let address_range = section.virtual_address..section.virtual_address + section.size;
+        if address_range.contains(&self.elf.header.entry_point) {
+            return Err(Error::SectionInUse(i, "entry point"));
+        }
+        if section.size != 0
+            && self.elf.segments.iter().any(|segment| {
+                // GNU_RELRO segment only changes the permissions of the LOAD segments.
+                !matches!(
+                    segment.kind,
+                    SegmentKind::Null | SegmentKind::Loadable | SegmentKind::GNU_RELRO
+                ) && segment.virtual_address < address_range.end
+                    && address_range.start < segment.virtual_address + segment.memory_size
+            })
+        {
+            return Err(Error::SectionInUse(i, "segment"));
+        }
+        if let Some(dynamic_table) = self.read_dynamic_table()? {
+            if dynamic_table
+                .iter()
+                .any(|(tag, value)| tag.is_address() && address_range.contains(value))
             {
-                let section = &self.elf.sections[dynamic_section_index];
-                self.file.seek(section.offset)?;
-                dynamic_table.write(
-                    &mut self.file,
-                    self.elf.header.class,
-                    self.elf.header.byte_order,
-                )?;
-                self.elf.segments.push(Segment {
-                    kind: SegmentKind::Dynamic,
-                    flags: SegmentFlags::READABLE | SegmentFlags::WRITABLE,
-                    offset: section.offset,
-                    virtual_address: section.virtual_address,
-                    physical_address: section.virtual_address,
-                    file_size: section.size,
-                    memory_size: section.size,
-                    align: section.align,
-                });
+                return Err(Error::SectionInUse(i, "dynamic table"));
             }
-            if old_dynamic_table_virtual_address != new_dynamic_table_virtual_address {
-                log::trace!(
-                    "Changed memory offset of the DYNAMIC segment from {:#x} to {:#x}",
-                    old_dynamic_table_virtual_address,
-                    new_dynamic_table_virtual_address
-                );
+        }
+        Ok(())
+    }
+
+    /// Update section indices after the section with index `removed` was removed.
+    ///
+    /// Removes the section from the section groups and removes the symbols that are defined in
+    /// this section.
+    fn renumber_sections(&mut self, removed: usize) -> Result<(), Error> {
+        let renumber = |index: u32| -> u32 {
+            if index as usize > removed {
+                index - 1
+            } else {
+                index
             }
-            log::trace!("Updated DYNAMIC section");
-            new_dynamic_table_virtual_address
         };
-        // Update symbol tables.
         for section in self.elf.sections.iter_mut() {
-            if !matches!(
-                section.kind,
-                SectionKind::SymbolTable | SectionKind::DynamicSymbolTable
-            ) {
-                continue;
+            section.link = renumber(section.link);
+            if info_is_section_index(section) {
+                section.info = renumber(section.info);
             }
-            self.file.seek(section.offset)?;
-            let mut symbol_table = SymbolTable::read(
-                &mut self.file,
-                self.elf.header.class,
-                self.elf.header.byte_order,
-                section.size,
-            )?;
-            let mut changed = false;
-            for symbol in symbol_table.iter_mut() {
-                if symbol.address == old_dynamic_table_virtual_address {
-                    log::trace!(
-                        "Changed dynamic table address from {:#x} to {:#x} in {:?}",
-                        symbol.address,
-                        new_dynamic_table_virtual_address,
-                        section.kind
+        }
+        self.elf.header.section_names_index =
+            renumber(self.elf.header.section_names_index.into()) as u16;
+        for i in 0..self.elf.sections.len() {
+            match self.elf.sections[i].kind {
+                SectionKind::Group => {
+                    let entries = self.read_u32_table(i)?;
+                    // The first entry contains the flags, the rest are the members.
+                    let Some((flags, members)) = entries.split_first() else {
+                        continue;
+                    };
+                    let mut new_entries = Vec::with_capacity(entries.len());
+                    new_entries.push(*flags);
+                    new_entries.extend(
+                        members
+                            .iter()
+                            .filter(|member| **member as usize != removed)
+                            .map(|member| renumber(*member)),
                     );
-                    symbol.address = new_dynamic_table_virtual_address;
+                    if new_entries != entries {
+                        self.write_u32_table(i, &new_entries)?;
+                    }
+                }
+                SectionKind::SymbolTable | SectionKind::DynamicSymbolTable => {
+                    self.renumber_symbols(i, removed)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the section indices of the symbols in section `table_index` after the section
+    /// with index `removed` was removed.
+    ///
+    /// The symbols that are defined in the removed section are removed as well.
+    /// [`check_section_references`](Self::check_section_references) makes sure that they aren't
+    /// used by the remaining sections.
+    fn renumber_symbols(&mut self, table_index: usize, removed: usize) -> Result<(), Error> {
+        let class = self.elf.header.class;
+        let byte_order = self.elf.header.byte_order;
+        let mut symbols: SymbolTable = self
+            .elf
+            .read_section_content(&self.elf.sections[table_index], &mut self.file)?;
+        let section_indices = self.read_symbol_section_indices(table_index, &symbols)?;
+        let (kept, dropped): (Vec<usize>, Vec<usize>) =
+            (0..symbols.len()).partition(|j| section_indices[*j] != removed);
+        let num_symbols = kept.len();
+        if !dropped.is_empty() {
+            log::trace!(
+                "Removing {} symbol(s) from section [{table_index}]",
+                dropped.len()
+            );
+            let permutation: Vec<usize> = kept.into_iter().chain(dropped).collect();
+            self.reorder_symbols(table_index, &mut symbols, 0, &permutation)?;
+            symbols.truncate(num_symbols);
+        }
+        let mut changed = num_symbols != section_indices.len();
+        for symbol in symbols.iter_mut() {
+            let index = symbol.section_index as usize;
+            if index > removed && index < SECTION_RESERVED_MIN {
+                symbol.section_index -= 1;
+                changed = true;
+            }
+        }
+        if let Some(i) = self.find_linked_section(SectionKind::SymbolTableIndex, table_index) {
+            let mut entries = self.read_u32_table(i)?;
+            entries.truncate(num_symbols);
+            let mut changed =
+                entries.len() * SYMTAB_SHNDX_LEN != self.elf.sections[i].size as usize;
+            for entry in entries.iter_mut() {
+                if *entry as usize > removed {
+                    *entry -= 1;
                     changed = true;
                 }
             }
             if changed {
-                self.file.seek(section.offset)?;
-                symbol_table.write(
-                    &mut self.file,
-                    self.elf.header.class,
-                    self.elf.header.byte_order,
-                )?;
+                self.write_u32_table(i, &entries)?;
             }
         }
+        if !changed {
+            return Ok(());
+        }
+        let size = (symbols.len() * class.symbol_len()) as u64;
+        let num_locals = symbols
+            .iter()
+            .take_while(|symbol| symbol.binding == SymbolBinding::Local)
+            .count();
+        let section = &mut self.elf.sections[table_index];
+        section.write_content(&mut self.file, class, byte_order, &symbols)?;
+        write_zeroes(&mut self.file, section.size.saturating_sub(size))?;
+        section.size = size;
+        section.info = num_locals
+            .try_into()
+            .map_err(|_| Error::TooBig("No. of local symbols"))?;
+        Ok(())
+    }
+
+    /// Get the section index of each symbol resolving the extended section indices.
+    fn read_symbol_section_indices(
+        &mut self,
+        table_index: usize,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<usize>, Error> {
+        let extended_indices =
+            match self.find_linked_section(SectionKind::SymbolTableIndex, table_index) {
+                Some(i) => self.read_u32_table(i)?,
+                None => Vec::new(),
+            };
+        Ok(symbols
+            .iter()
+            .enumerate()
+            .map(|(j, symbol)| match extended_indices.get(j) {
+                Some(index) if symbol.section_index == SECTION_XINDEX => *index as usize,
+                _ => symbol.section_index as usize,
+            })
+            .collect())
+    }
+
+    /// Read the content of section `i` as an array of `u32` values.
+    fn read_u32_table(&mut self, i: usize) -> Result<Vec<u32>, Error> {
+        let byte_order = self.elf.header.byte_order;
+        let bytes: Vec<u8> = self
+            .elf
+            .read_section_content(&self.elf.sections[i], &mut self.file)?;
+        let mut reader = &bytes[..];
+        (0..bytes.len() / 4)
+            .map(|_| reader.read_u32(byte_order))
+            .collect()
+    }
+
+    /// Replace the content of section `i` with the array of `u32` values.
+    ///
+    /// The array can't be longer than the original content.
+    fn write_u32_table(&mut self, i: usize, entries: &[u32]) -> Result<(), Error> {
+        let byte_order = self.elf.header.byte_order;
+        let section = &mut self.elf.sections[i];
+        let size = (entries.len() * 4) as u64;
+        self.file.seek(section.offset)?;
+        for entry in entries.iter() {
+            self.file.write_u32(byte_order, *entry)?;
+        }
+        write_zeroes(&mut self.file, section.size.saturating_sub(size))?;
+        section.size = size;
         Ok(())
     }
 
@@ -513,7 +2412,8 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
                         .try_into()
                         .map_err(|_| Error::TooBig("Section name"))?,
                     kind: SectionKind::StringTable,
-                    flags: SectionFlags::ALLOC,
+                    // Section names are not loaded into memory.
+                    flags: SectionFlags::empty(),
                     virtual_address: 0,
                     offset: 0,
                     size,
@@ -538,59 +2438,38 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
         Ok(name_offset)
     }
 
-    fn get_string_offset(
+    /// Move the string table to a new section that is large enough to hold the `table`.
+    ///
+    /// Returns the index of the new section.
+    fn realloc_string_table(
         &mut self,
-        string: &CStr,
-        table_section_index: Option<usize>,
+        table_section_index: usize,
         table_name: &CStr,
-        table: &mut StringTable,
-    ) -> Result<(usize, usize), Error> {
-        let (string_offset, table_section_index) = match table.get_offset(string) {
-            Some(string_offset) => {
-                log::trace!(
-                    "Found string {:?} in {:?} at offset {}",
-                    string,
-                    table_name,
-                    string_offset
-                );
-                (string_offset, table_section_index.expect("Should be set"))
-            }
-            None => {
-                if let Some(table_section_index) = table_section_index {
-                    self.free_section(table_section_index, table_name)?;
-                }
-                let outer_string_offset = table.insert(string);
-                log::trace!(
-                    "Adding string {:?} to {:?} at offset {}",
-                    string,
-                    table_name,
-                    outer_string_offset
-                );
-                let name_offset = self.get_name_offset(table_name)?;
-                let i = self.alloc_section(Section {
-                    name_offset: name_offset
-                        .try_into()
-                        .map_err(|_| Error::TooBig("Section name"))?,
-                    kind: SectionKind::StringTable,
-                    flags: SectionFlags::ALLOC,
-                    virtual_address: 0,
-                    offset: 0,
-                    size: table.as_bytes().len() as u64,
-                    link: 0,
-                    info: 0,
-                    align: STRING_TABLE_ALIGN,
-                    entry_len: 0,
-                })?;
-                self.elf.sections[i].write_content(
-                    &mut self.file,
-                    self.elf.header.class,
-                    self.elf.header.byte_order,
-                    &table,
-                )?;
-                (outer_string_offset, i)
-            }
-        };
-        Ok((string_offset, table_section_index))
+        table: &StringTable,
+    ) -> Result<usize, Error> {
+        self.free_section(table_section_index, table_name)?;
+        let name_offset = self.get_name_offset(table_name)?;
+        let i = self.alloc_section(Section {
+            name_offset: name_offset
+                .try_into()
+                .map_err(|_| Error::TooBig("Section name"))?,
+            kind: SectionKind::StringTable,
+            flags: SectionFlags::ALLOC,
+            virtual_address: 0,
+            offset: 0,
+            size: table.as_bytes().len() as u64,
+            link: 0,
+            info: 0,
+            align: STRING_TABLE_ALIGN,
+            entry_len: 0,
+        })?;
+        self.elf.sections[i].write_content(
+            &mut self.file,
+            self.elf.header.class,
+            self.elf.header.byte_order,
+            table,
+        )?;
+        Ok(i)
     }
 
     fn free_segment(&mut self, i: usize) -> Result<(), Error> {
@@ -635,7 +2514,9 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
             &self.elf.sections,
             &mut self.elf.segments,
         );
-        alloc.allocate_segment(&mut segment)?;
+        if segment.file_size != 0 || segment.memory_size != 0 {
+            alloc.allocate_segment(&mut segment)?;
+        }
         /*
         segment.virtual_address = self
             .alloc_memory_block(segment.memory_size, segment.align)
@@ -732,7 +2613,14 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
             &self.elf.sections,
             &mut self.elf.segments,
         );
-        alloc.allocate_section(&mut section)?;
+        if section.flags.contains(SectionFlags::ALLOC) {
+            alloc.allocate_section(&mut section)?;
+        } else {
+            section.offset = alloc
+                .allocate_file_space(section.size, section.align)
+                .ok_or(Error::FileSpaceAlloc)?;
+            section.virtual_address = 0;
+        }
         let i = self.elf.sections.add(section);
         if log_enabled!(Level::Trace) {
             let names = get_section_names!(self);
@@ -782,6 +2670,74 @@ impl<F: ElfRead + ElfWrite + ElfSeek> ElfPatcher<F> {
     }
 }
 
+/// Get in-file offset range of the table with `num_entries` entries of `entry_len` bytes each.
+///
+/// Returns `None` on overflow.
+fn table_range(offset: u64, num_entries: usize, entry_len: u16) -> Option<Range<u64>> {
+    let len = (num_entries as u64).checked_mul(entry_len.into())?;
+    Some(offset..offset.checked_add(len)?)
+}
+
+/// Check that non-empty ranges overlap.
+fn ranges_overlap(a: &Range<u64>, b: &Range<u64>) -> bool {
+    !a.is_empty() && !b.is_empty() && a.start < b.end && b.start < a.end
+}
+
+/// Location of the dynamic table.
+#[derive(Debug, Clone, Copy)]
+enum DynamicLocation {
+    /// The index of `.dynamic` section.
+    Section(usize),
+    /// The index of `DYNAMIC` segment. Used when there is no `.dynamic` section.
+    Segment(usize),
+}
+
+/// Decode packed relative relocations (`DT_RELR`) into the list of addresses.
+fn decode_relr(entries: &[u64], word_len: u64, addresses: &mut Vec<u64>) {
+    let num_bits = word_len * 8 - 1;
+    let mut base: u64 = 0;
+    for entry in entries.iter() {
+        if entry & 1 == 0 {
+            // Address.
+            addresses.push(*entry);
+            base = entry.wrapping_add(word_len);
+        } else {
+            // Bitmap of the following words.
+            for i in 0..num_bits {
+                if (entry >> (i + 1)) & 1 != 0 {
+                    addresses.push(base.wrapping_add(i * word_len));
+                }
+            }
+            base = base.wrapping_add(num_bits * word_len);
+        }
+    }
+}
+
+const fn addend_to_address(addend: i64, class: Class) -> u64 {
+    match class {
+        Class::Elf32 => addend as u32 as u64,
+        Class::Elf64 => addend as u64,
+    }
+}
+
+const fn address_to_addend(address: u64, class: Class) -> i64 {
+    match class {
+        Class::Elf32 => address as u32 as i32 as i64,
+        Class::Elf64 => address as i64,
+    }
+}
+
+fn is_debug_section_name(name: &CStr) -> bool {
+    let name = name.to_bytes();
+    name.starts_with(b".debug_") || name.starts_with(b".zdebug_")
+}
+
+/// Returns `true` if `info` field of the section is a section index.
+fn info_is_section_index(section: &Section) -> bool {
+    section.flags.contains(SectionFlags::INFO_LINK)
+        || matches!(section.kind, SectionKind::RelTable | SectionKind::RelaTable)
+}
+
 macro_rules! get_section_names {
     ($self: ident) => {{
         if $self.names.is_none() {
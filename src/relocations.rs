@@ -14,6 +14,7 @@ use crate::Error;
 /// Relocation without an addend.
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rel {
     /// The offset from the beginning of the section.
     pub offset: u64,
@@ -83,6 +84,7 @@ impl EntityIo for Rel {
 /// Relocation with an addend.
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RelA {
     /// Relocation without an addend.
     pub rel: Rel,
@@ -127,6 +129,8 @@ macro_rules! define_rel_table {
     ($table: ident, $rel: ident, $rel_len: ident) => {
         #[derive(Default)]
         #[cfg_attr(test, derive(PartialEq, Eq, Debug))]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "serde", serde(transparent))]
         /// Relocation table.
         pub struct $table {
             entries: Vec<$rel>,
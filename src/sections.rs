@@ -11,6 +11,7 @@ use crate::BlockWrite;
 use crate::ByteOrder;
 use crate::Class;
 use crate::ElfRead;
+use crate::ElfReadAt;
 use crate::ElfSeek;
 use crate::ElfWrite;
 use crate::EntityIo;
@@ -18,13 +19,16 @@ use crate::Error;
 use crate::FileKind;
 use crate::Header;
 use crate::ProgramHeader;
+use crate::ReadAtCursor;
 use crate::SectionFlags;
 use crate::SectionKind;
 use crate::SegmentKind;
 
 /// Sections.
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct SectionHeader {
     entries: Vec<Section>,
 }
@@ -84,7 +88,7 @@ impl SectionHeader {
         writer: &mut W,
         i: usize,
     ) -> Result<Section, Error> {
-        let section = std::mem::take(&mut self.entries[i]);
+        let section = core::mem::take(&mut self.entries[i]);
         log::trace!(
             "Freeing file block {:#x}..{:#x}",
             section.offset,
@@ -176,8 +180,9 @@ impl DerefMut for SectionHeader {
 /// Dynamic loader maps sections into virtual address space of a program as part of segments.
 /// Usually sections are part of [segments](crate::Segment), however, some section types exist on
 /// their own.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
     /// Offset of the section name in the section that stores section names.
     ///
@@ -280,6 +285,10 @@ impl EntityIo for Section {
 
 impl Section {
     /// Read section contents as bytes.
+    ///
+    /// Doesn't check [`Limits`](crate::Limits): the amount of memory allocated depends on the
+    /// section size that is stored in the file. Use
+    /// [`Elf::read_section_content`](crate::Elf::read_section_content) for untrusted files.
     pub fn read_content<R: ElfRead + ElfSeek, T: BlockRead>(
         &self,
         reader: &mut R,
@@ -290,6 +299,23 @@ impl Section {
         T::read(reader, class, byte_order, self.size)
     }
 
+    /// Read section contents as bytes using positional reads.
+    ///
+    /// Doesn't require mutable access to `reader`, i.e. the same file can be shared between
+    /// multiple threads.
+    ///
+    /// Doesn't check [`Limits`](crate::Limits), see [`read_content`](Self::read_content).
+    pub fn read_content_at<R: ElfReadAt + ?Sized, T: BlockRead>(
+        &self,
+        reader: &R,
+        class: Class,
+        byte_order: ByteOrder,
+    ) -> Result<T, Error> {
+        let mut cursor = ReadAtCursor::new(reader);
+        cursor.seek(self.offset)?;
+        T::read(&mut cursor, class, byte_order, self.size)
+    }
+
     /// Write section contents.
     pub fn write_content<W: ElfWrite + ElfSeek, T: BlockWrite + ?Sized>(
         &self,
@@ -305,6 +331,10 @@ impl Section {
 
     /// Zero out the entry's content.
     pub fn clear_content<W: ElfWrite + ElfSeek>(&self, writer: &mut W) -> Result<(), Error> {
+        if self.kind == SectionKind::NoBits {
+            // The section doesn't occupy any space in the file.
+            return Ok(());
+        }
         zero(writer, self.offset, self.size)?;
         Ok(())
     }
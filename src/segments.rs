@@ -14,17 +14,21 @@ use crate::BlockWrite;
 use crate::ByteOrder;
 use crate::Class;
 use crate::ElfRead;
+use crate::ElfReadAt;
 use crate::ElfSeek;
 use crate::ElfWrite;
 use crate::EntityIo;
 use crate::Error;
 use crate::Header;
+use crate::ReadAtCursor;
 use crate::SegmentFlags;
 use crate::SegmentKind;
 
 /// Segments.
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ProgramHeader {
     entries: Vec<Segment>,
 }
@@ -165,7 +169,7 @@ impl ProgramHeader {
         Ok(())
     }
 
-    fn check_entry_point(&self, entry_point: u64) -> Result<(), Error> {
+    pub(crate) fn check_entry_point(&self, entry_point: u64) -> Result<(), Error> {
         if entry_point != 0
             && !self.entries.iter().any(|segment| {
                 segment.kind == SegmentKind::Loadable
@@ -303,8 +307,9 @@ impl DerefMut for ProgramHeader {
 /// Dynamic loader maps segments into virtual address space of a program.
 /// Usually segments consists of [sections](crate::Section), however, some segment types exist on
 /// their own.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Segment {
     /// Segment type.
     pub kind: SegmentKind,
@@ -389,6 +394,10 @@ impl EntityIo for Segment {
 
 impl Segment {
     /// Read segment contents as bytes.
+    ///
+    /// Doesn't check [`Limits`](crate::Limits): the amount of memory allocated depends on the
+    /// file size of the segment that is stored in the file. Check
+    /// [`file_size`](Self::file_size) before reading untrusted files.
     pub fn read_content<R: ElfRead + ElfSeek, T: BlockRead>(
         &self,
         reader: &mut R,
@@ -399,6 +408,23 @@ impl Segment {
         T::read(reader, class, byte_order, self.file_size)
     }
 
+    /// Read segment contents as bytes using positional reads.
+    ///
+    /// Doesn't require mutable access to `reader`, i.e. the same file can be shared between
+    /// multiple threads.
+    ///
+    /// Doesn't check [`Limits`](crate::Limits), see [`read_content`](Self::read_content).
+    pub fn read_content_at<R: ElfReadAt + ?Sized, T: BlockRead>(
+        &self,
+        reader: &R,
+        class: Class,
+        byte_order: ByteOrder,
+    ) -> Result<T, Error> {
+        let mut cursor = ReadAtCursor::new(reader);
+        cursor.seek(self.offset)?;
+        T::read(&mut cursor, class, byte_order, self.file_size)
+    }
+
     /// Write segment contents.
     pub fn write_content<W: ElfWrite + ElfSeek, T: BlockWrite + ?Sized>(
         &self,
@@ -510,7 +536,7 @@ impl Segment {
     }
 }
 
-const fn align_is_valid(align: u64) -> bool {
+pub(crate) const fn align_is_valid(align: u64) -> bool {
     align == 0 || align.is_power_of_two()
 }
 
@@ -2,6 +2,7 @@ use alloc::vec;
 use alloc::vec::Vec;
 use core::ffi::CStr;
 
+use crate::read_bytes_chunked;
 use crate::BlockRead;
 use crate::ByteOrder;
 use crate::Class;
@@ -85,8 +86,7 @@ impl StringTable {
 
     /// Read the table from the `reader`.
     pub fn read<R: ElfRead>(reader: &mut R, len: u64) -> Result<Self, Error> {
-        let mut strings = vec![0_u8; len as usize];
-        reader.read_bytes(&mut strings[..])?;
+        let strings = read_bytes_chunked(reader, len)?;
         Ok(Self(strings))
     }
 
@@ -17,6 +17,7 @@ use crate::SymbolVisibility;
 /// A symbol.
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Symbol {
     /// Address.
     pub address: u64,
@@ -35,6 +36,12 @@ pub struct Symbol {
 }
 
 impl Symbol {
+    /// Section index of undefined symbols.
+    pub const UNDEFINED: u16 = 0;
+
+    /// Section index of symbols which values are absolute, i.e. not affected by relocations.
+    pub const ABSOLUTE: u16 = 0xfff1;
+
     const fn info(&self) -> u8 {
         self.binding.to_info_bits() | self.kind.to_info_bits()
     }
@@ -113,6 +120,8 @@ impl EntityIo for Symbol {
 /// Symbol table.
 #[derive(Default)]
 #[cfg_attr(test, derive(PartialEq, Eq, Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct SymbolTable {
     entries: Vec<Symbol>,
 }
@@ -0,0 +1,348 @@
+use core::ops::Deref;
+use core::ops::DerefMut;
+use std::ffi::OsString;
+use std::fs;
+#[cfg(unix)]
+use std::io::ErrorKind;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::vec::Vec;
+
+use crate::Cursor;
+use crate::Elf;
+use crate::ElfPatcher;
+use crate::Error;
+
+/// Patches ELF file in memory and replaces the original file atomically.
+///
+/// The file is loaded into a growable in-memory buffer and all the modifications are made via
+/// [`ElfPatcher`] that this type dereferences to. On [`commit`](Self::commit) the result is
+/// written to a temporary file in the same directory that is then renamed over the original
+/// file. The original file stays untouched if any step fails or the transaction is dropped.
+pub struct PatchTransaction {
+    path: PathBuf,
+    patcher: ElfPatcher<Cursor<Vec<u8>>>,
+    sync: bool,
+}
+
+impl PatchTransaction {
+    /// Load the file into memory and start patching.
+    pub fn open<P: Into<PathBuf>>(path: P, page_size: u64) -> Result<Self, Error> {
+        let path = path.into();
+        let bytes = fs::read(&path)?;
+        let mut cursor = Cursor::new(bytes);
+        let elf = Elf::read(&mut cursor, page_size)?;
+        let patcher = ElfPatcher::new(elf, cursor);
+        Ok(Self {
+            path,
+            patcher,
+            sync: true,
+        })
+    }
+
+    /// Sync the new file and its directory to disk on commit.
+    ///
+    /// Enabled by default. Disabling makes commits faster but the new contents might be lost on
+    /// power failure. The replacement is atomic either way.
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    /// Get the path of the file being patched.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Finish patching and replace the original file.
+    ///
+    /// See [`ElfPatcher::finish`].
+    pub fn commit(self) -> Result<(), Error> {
+        let cursor = self.patcher.finish()?;
+        do_replace_file(&self.path, cursor.get_ref(), self.sync)
+    }
+
+    /// Finish patching, remove the unused space at the end of the file and replace the
+    /// original file.
+    ///
+    /// See [`ElfPatcher::finish_and_truncate`].
+    pub fn commit_and_truncate(self) -> Result<(), Error> {
+        let cursor = self.patcher.finish_and_truncate()?;
+        do_replace_file(&self.path, cursor.get_ref(), self.sync)
+    }
+}
+
+impl Deref for PatchTransaction {
+    type Target = ElfPatcher<Cursor<Vec<u8>>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.patcher
+    }
+}
+
+impl DerefMut for PatchTransaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.patcher
+    }
+}
+
+/// Atomically replace the contents of the file.
+///
+/// Writes `contents` to a temporary file in the same directory, copies ownership, permissions and
+/// extended attributes (file capabilities, SELinux labels, ACLs) from the original file, syncs the
+/// temporary file to disk and renames it over the original file. Symbolic links are resolved, i.e.
+/// the file they point to is replaced. Files with multiple hard links are not replaced because
+/// the rename would break the links.
+///
+/// The original file stays untouched if any step before the rename fails, including failure to
+/// preserve any of the metadata.
+pub fn replace_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    do_replace_file(path, contents, true)
+}
+
+fn do_replace_file(path: &Path, contents: &[u8], sync: bool) -> Result<(), Error> {
+    let path = fs::canonicalize(path)?;
+    let original = fs::File::open(&path)?;
+    let metadata = original.metadata()?;
+    #[cfg(unix)]
+    if metadata.nlink() > 1 {
+        return Err(Error::HardLinks(metadata.nlink()));
+    }
+    let temporary_path = temporary_path(&path);
+    let result = (|| -> Result<(), Error> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary_path)?;
+        file.write_all(contents)?;
+        #[cfg(unix)]
+        copy_ownership(&metadata, &file)?;
+        // Changing the owner clears set-user-ID and set-group-ID bits, hence the order.
+        file.set_permissions(metadata.permissions())?;
+        #[cfg(unix)]
+        copy_xattrs(&original, &file)?;
+        if sync {
+            file.sync_all()?;
+        }
+        drop(file);
+        fs::rename(&temporary_path, &path)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temporary_path);
+        return Err(e);
+    }
+    // Persist the rename.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|_| sync) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Copy file contents, ownership, permissions and extended attributes.
+///
+/// Fails if the ownership (e.g. when copying other user's file without privileges) or any of
+/// the extended attributes (e.g. file capabilities) can't be preserved; the copy is removed in
+/// this case. Input/output errors include both paths.
+pub fn copy_file(from: &Path, to: &Path) -> Result<(), Error> {
+    fs::copy(from, to).map_err(|e| copy_error(e, from, to))?;
+    #[cfg(unix)]
+    {
+        let result = (|| -> Result<(), Error> {
+            let source = fs::File::open(from)?;
+            let target = fs::File::open(to)?;
+            let metadata = source.metadata()?;
+            copy_ownership(&metadata, &target)?;
+            // Changing the owner clears set-user-ID and set-group-ID bits, hence the order.
+            target.set_permissions(metadata.permissions())?;
+            copy_xattrs(&source, &target)
+        })();
+        if let Err(e) = result {
+            let _ = fs::remove_file(to);
+            return Err(match e {
+                Error::Io(e) => Error::Io(copy_error(e, from, to)),
+                e => e,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Add the paths to the error message.
+fn copy_error(e: std::io::Error, from: &Path, to: &Path) -> std::io::Error {
+    std::io::Error::new(
+        e.kind(),
+        std::format!("{e} when copying {} to {}", from.display(), to.display()),
+    )
+}
+
+#[cfg(unix)]
+fn copy_ownership(metadata: &fs::Metadata, file: &fs::File) -> Result<(), Error> {
+    let uid = metadata.uid();
+    let gid = metadata.gid();
+    let new_metadata = file.metadata()?;
+    if new_metadata.uid() == uid && new_metadata.gid() == gid {
+        return Ok(());
+    }
+    std::os::unix::fs::fchown(file, Some(uid), Some(gid))
+        .map_err(|e| Error::PreserveMetadata("ownership".into(), e))
+}
+
+#[cfg(unix)]
+fn copy_xattrs(source: &fs::File, target: &fs::File) -> Result<(), Error> {
+    use xattr::FileExt;
+    let names = match source.list_xattr() {
+        Ok(names) => names,
+        // Nothing to preserve.
+        Err(e) if e.kind() == ErrorKind::Unsupported => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for name in names {
+        let Some(value) = source.get_xattr(&name)? else {
+            continue;
+        };
+        target.set_xattr(&name, &value[..]).map_err(|e| {
+            Error::PreserveMetadata(std::format!("extended attribute {:?}", name), e)
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns hidden temporary file path in the same directory as `path`.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(std::format!(".{}.tmp", std::process::id()));
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_file() {
+        let workdir = tempfile::tempdir().unwrap();
+        let path = workdir.path().join("file");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        replace_file(&path, b"new contents").unwrap();
+        assert_eq!(b"new contents", &fs::read(&path).unwrap()[..]);
+        assert_eq!(
+            0o751,
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+        // No temporary files left.
+        assert_eq!(1, fs::read_dir(workdir.path()).unwrap().count());
+        // The original file is untouched on error.
+        let missing = workdir.path().join("missing").join("file");
+        assert!(replace_file(&missing, b"new").is_err());
+        // Symbolic links are preserved.
+        let link = workdir.path().join("link");
+        std::os::unix::fs::symlink("file", &link).unwrap();
+        replace_file(&link, b"via link").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(b"via link", &fs::read(&path).unwrap()[..]);
+        // Hard links are not broken.
+        let hard_link = workdir.path().join("hard-link");
+        fs::hard_link(&path, &hard_link).unwrap();
+        assert!(matches!(
+            replace_file(&path, b"new"),
+            Err(Error::HardLinks(2))
+        ));
+        assert_eq!(b"via link", &fs::read(&hard_link).unwrap()[..]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_xattrs() {
+        let workdir = tempfile::tempdir().unwrap();
+        let path = workdir.path().join("file");
+        fs::write(&path, b"old").unwrap();
+        if xattr::set(&path, "user.elb", b"value").is_err() {
+            // Extended attributes are not supported by the file system.
+            return;
+        }
+        replace_file(&path, b"new").unwrap();
+        assert_eq!(
+            Some(b"value".to_vec()),
+            xattr::get(&path, "user.elb").unwrap()
+        );
+        let copy = workdir.path().join("copy");
+        copy_file(&path, &copy).unwrap();
+        assert_eq!(b"new", &fs::read(&copy).unwrap()[..]);
+        assert_eq!(
+            Some(b"value".to_vec()),
+            xattr::get(&copy, "user.elb").unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_file() {
+        let workdir = tempfile::tempdir().unwrap();
+        let path = workdir.path().join("file");
+        fs::write(&path, b"contents").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        let copy = workdir.path().join("copy");
+        copy_file(&path, &copy).unwrap();
+        assert_eq!(b"contents", &fs::read(&copy).unwrap()[..]);
+        let metadata = fs::metadata(&copy).unwrap();
+        assert_eq!(0o751, metadata.permissions().mode() & 0o777);
+        assert_eq!(fs::metadata(&path).unwrap().uid(), metadata.uid());
+        // Only privileged user can change the owner.
+        if metadata.uid() == 0 {
+            std::os::unix::fs::chown(&path, Some(1), Some(1)).unwrap();
+            fs::remove_file(&copy).unwrap();
+            copy_file(&path, &copy).unwrap();
+            let metadata = fs::metadata(&copy).unwrap();
+            assert_eq!((1, 1), (metadata.uid(), metadata.gid()));
+        }
+        // The error message contains the paths.
+        let missing = workdir.path().join("missing");
+        let error = std::format!("{}", copy_file(&missing, &copy).unwrap_err());
+        assert!(error.contains(&*missing.to_string_lossy()), "{error}");
+    }
+
+    #[test]
+    fn test_transaction() {
+        let workdir = tempfile::tempdir().unwrap();
+        let path = workdir.path().join("exe");
+        fs::copy(std::env::current_exe().unwrap(), &path).unwrap();
+        let original = fs::read(&path).unwrap();
+        let mut transaction = PatchTransaction::open(&path, 4096).unwrap();
+        transaction.set_executable_stack(false).unwrap();
+        // Dropped transaction doesn't modify the file.
+        drop(transaction);
+        assert_eq!(original, fs::read(&path).unwrap());
+        let mut transaction = PatchTransaction::open(&path, 4096).unwrap();
+        transaction
+            .add_section(
+                c".elb",
+                crate::SectionKind::ProgramBits,
+                crate::SectionFlags::empty(),
+                1,
+                b"hello",
+            )
+            .unwrap();
+        transaction.commit().unwrap();
+        let mut file = fs::File::open(&path).unwrap();
+        let elf = Elf::read(&mut file, 4096).unwrap();
+        assert_eq!(
+            Some(b"hello".to_vec()),
+            elf.read_section(
+                c".elb",
+                &elf.read_section_names(&mut file).unwrap().unwrap(),
+                &mut file
+            )
+            .unwrap()
+        );
+    }
+}
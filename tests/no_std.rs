@@ -0,0 +1,47 @@
+#![allow(missing_docs)]
+
+//! Reads and patches ELF held in memory using only the API that is available without `std`.
+
+use elb::Cursor;
+use elb::DynamicTag;
+use elb::Elf;
+use elb::ElfPatcher;
+
+#[test]
+fn patch_in_memory() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let section = &elf.sections[1];
+    let content: Vec<u8> = section
+        .read_content(&mut cursor, elf.header.class, elf.header.byte_order)
+        .unwrap();
+    assert_eq!(section.size, content.len() as u64);
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_interpreter(c"/lib/ld-elb-no-std.so.1").unwrap();
+    patcher
+        .set_dynamic_tag(DynamicTag::Runpath, c"/opt/elb/lib")
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    assert_eq!(
+        Some(c"/lib/ld-elb-no-std.so.1".into()),
+        elf.read_interpreter(&mut cursor).unwrap()
+    );
+    let dynamic_table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    let strings = elf.read_dynamic_string_table(&mut cursor).unwrap().unwrap();
+    let offset = dynamic_table.get(DynamicTag::Runpath).unwrap();
+    assert_eq!(Some(c"/opt/elb/lib"), strings.get_string(offset as usize));
+}
+
+#[test]
+fn write_to_slice() {
+    let mut buf = [0_u8; 4];
+    let mut cursor = Cursor::new(&mut buf[..]);
+    elb::ElfSeek::seek(&mut cursor, 2).unwrap();
+    elb::ElfWrite::write_bytes(&mut cursor, &[1, 2]).unwrap();
+    assert!(elb::ElfWrite::write_bytes(&mut cursor, &[3]).is_err());
+    assert_eq!([0, 0, 1, 2], buf);
+}
+
+const PAGE_SIZE: u64 = 4096;
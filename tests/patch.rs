@@ -0,0 +1,1148 @@
+#![allow(clippy::unwrap_used)]
+#![allow(missing_docs)]
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use elb::debuglink_crc32;
+use elb::ArmFlags;
+use elb::Cursor;
+use elb::DebugLink;
+use elb::DryRun;
+use elb::DynamicFlags1;
+use elb::DynamicTag;
+use elb::Elf;
+use elb::ElfChange;
+use elb::ElfPatcher;
+use elb::Error;
+use elb::Limits;
+use elb::Machine;
+use elb::OsAbi;
+use elb::PatchPolicy;
+use elb::RelaTable;
+use elb::SectionFlags;
+use elb::SectionKind;
+use elb::Segment;
+use elb::SegmentFlags;
+use elb::SegmentKind;
+use elb::StringTable;
+use elb::SymbolBinding;
+use elb::SymbolTable;
+use elb::SymbolVisibility;
+
+#[test]
+fn add_remove_replace_needed() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let original = read_needed(&elf, &mut cursor);
+    let Some(first) = original.first().cloned() else {
+        return;
+    };
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.replace_needed(&first, c"libelb-test.so").unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut expected = original.clone();
+    expected[0] = c"libelb-test.so".into();
+    assert_eq!(expected, read_needed(&elf, &mut cursor));
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.add_needed(&first, 1).unwrap();
+    // Adding the same library twice does nothing.
+    patcher.add_needed(&first, 0).unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    expected.insert(1, first.clone());
+    assert_eq!(expected, read_needed(&elf, &mut cursor));
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.remove_needed(c"libelb-test.so").unwrap();
+    // Removing non-existent library does nothing.
+    patcher.remove_needed(c"libelb-test.so").unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    assert_eq!(original, read_needed(&elf, &mut cursor));
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn edit_dynamic_table() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let Some(old_section) = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::Dynamic)
+        .cloned()
+    else {
+        return;
+    };
+    let old_table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    // Removing the entries doesn't move the table.
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.remove_dynamic_tag(DynamicTag::Debug).unwrap();
+    patcher
+        .edit_dynamic_table(|table| {
+            let flags = table.get(DynamicTag::FLAGS_1).unwrap_or(0);
+            let flags = DynamicFlags1::from_bits_retain(flags) | DynamicFlags1::NODELETE;
+            table.set(DynamicTag::FLAGS_1, flags.bits());
+        })
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let section = dynamic_section(&elf);
+    let table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    if old_table.get(DynamicTag::FLAGS_1).is_some() || old_table.get(DynamicTag::Debug).is_some() {
+        assert_eq!(old_section.offset, section.offset);
+        assert_eq!(old_section.size, section.size);
+    }
+    assert_eq!(None, table.get(DynamicTag::Debug));
+    let flags = DynamicFlags1::from_bits_retain(table.get(DynamicTag::FLAGS_1).unwrap());
+    assert!(flags.contains(DynamicFlags1::NODELETE));
+    // Adding many entries moves the table.
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    assert!(matches!(
+        patcher.add_dynamic_entry(DynamicTag::Null, 0),
+        Err(Error::InvalidDynamicEntryKind(0))
+    ));
+    patcher
+        .insert_dynamic_entry(0, DynamicTag::Debug, 0)
+        .unwrap();
+    for _ in 0..32 {
+        patcher.add_dynamic_entry(DynamicTag::BindNow, 0).unwrap();
+    }
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let section = dynamic_section(&elf);
+    assert_ne!(old_section.virtual_address, section.virtual_address);
+    let table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    assert_eq!(Some(&(DynamicTag::Debug, 0)), table.iter().next());
+    assert_eq!(
+        32,
+        table
+            .iter()
+            .filter(|(tag, _)| *tag == DynamicTag::BindNow)
+            .count()
+    );
+    // `_DYNAMIC` points to the new location.
+    for symbol_table_section in elf.sections.iter() {
+        if symbol_table_section.kind != SectionKind::SymbolTable {
+            continue;
+        }
+        let symbols: SymbolTable = symbol_table_section
+            .read_content(&mut cursor, elf.header.class, elf.header.byte_order)
+            .unwrap();
+        let strings: StringTable = elf.sections[symbol_table_section.link as usize]
+            .read_content(&mut cursor, elf.header.class, elf.header.byte_order)
+            .unwrap();
+        for symbol in symbols.iter() {
+            if strings.get_string(symbol.name_offset as usize) == Some(c"_DYNAMIC") {
+                assert_eq!(section.virtual_address, symbol.address);
+            }
+        }
+    }
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn move_dynamic_table() {
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    fs_err::write(dir.join("main.c"), DYNAMIC_MAIN).unwrap();
+    for (name, args) in [
+        ("main", &[][..]),
+        ("main-relr", &["-Wl,-z,pack-relative-relocs"][..]),
+    ] {
+        let status = Command::new("cc")
+            .args(args)
+            .args(["-o", name, "main.c"])
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        if !status.success() {
+            // Old linkers don't support packed relocations.
+            assert_ne!("main", name);
+            continue;
+        }
+        let path = dir.join(name);
+        let mut cursor = Cursor::new(fs_err::read(&path).unwrap());
+        let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+        let old_section = dynamic_section(&elf);
+        let mut patcher = ElfPatcher::new(elf, cursor);
+        patcher
+            .set_dynamic_tag(DynamicTag::Runpath, c"/elb")
+            .unwrap();
+        for _ in 0..32 {
+            patcher.add_dynamic_entry(DynamicTag::BindNow, 0).unwrap();
+        }
+        let mut cursor = patcher.finish().unwrap();
+        let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+        let section = dynamic_section(&elf);
+        assert_ne!(old_section.virtual_address, section.virtual_address);
+        // `_GLOBAL_OFFSET_TABLE_[0]` points to the new location.
+        let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+        let got_entries: Vec<u64> = [c".got", c".got.plt"]
+            .into_iter()
+            .filter_map(|name| elf.read_section(name, &names, &mut cursor).unwrap())
+            .filter_map(|bytes| Some(u64::from_ne_bytes(bytes.get(..8)?.try_into().ok()?)))
+            .collect();
+        assert!(got_entries.contains(&section.virtual_address));
+        assert!(!got_entries.contains(&old_section.virtual_address));
+        // The relative relocation of `dynamic` variable points to the new location.
+        fs_err::write(&path, cursor.into_inner()).unwrap();
+        let output = Command::new(&path).output().unwrap();
+        assert!(output.status.success(), "{name}");
+        assert_eq!(b"runpath\n", &output.stdout[..]);
+    }
+}
+
+#[test]
+fn patch_without_section_header() {
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    fs_err::write(dir.join("main.c"), DYNAMIC_MAIN).unwrap();
+    let status = Command::new("cc")
+        .args(["-o", "main", "main.c"])
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let path = dir.join("main");
+    // Remove section header.
+    let mut cursor = Cursor::new(fs_err::read(&path).unwrap());
+    let mut elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    elf.sections.clear();
+    elf.header.section_header_offset = 0;
+    elf.header.num_sections = 0;
+    elf.header.section_names_index = 0;
+    elf.write(&mut cursor).unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let old_interpreter = elf.read_interpreter(&mut cursor).unwrap().unwrap();
+    // Use longer but equivalent path to force relocation.
+    let mut interpreter = b"/".repeat(64);
+    interpreter.extend_from_slice(old_interpreter.to_bytes());
+    let interpreter = CString::new(interpreter).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_interpreter(&interpreter).unwrap();
+    patcher
+        .set_dynamic_tag(DynamicTag::Runpath, c"/elb")
+        .unwrap();
+    for _ in 0..32 {
+        patcher.add_dynamic_entry(DynamicTag::BindNow, 0).unwrap();
+    }
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    assert_eq!(0, elf.header.num_sections);
+    assert_eq!(0, elf.header.section_header_offset);
+    assert!(elf.sections.is_empty());
+    assert_eq!(
+        Some(interpreter),
+        elf.read_interpreter(&mut cursor).unwrap()
+    );
+    let dynamic_table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    let strings = elf.read_dynamic_string_table(&mut cursor).unwrap().unwrap();
+    let runpath = dynamic_table.get(DynamicTag::Runpath).unwrap();
+    assert_eq!(Some(c"/elb"), strings.get_string(runpath as usize));
+    fs_err::write(&path, cursor.into_inner()).unwrap();
+    let output = Command::new(&path).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(b"runpath\n", &output.stdout[..]);
+}
+
+#[test]
+fn add_section() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher
+        .add_section(
+            c".elb.sbom",
+            SectionKind::ProgramBits,
+            SectionFlags::empty(),
+            1,
+            br#"{"name":"elb"}"#,
+        )
+        .unwrap();
+    patcher
+        .add_section(
+            c".elb.license",
+            SectionKind::ProgramBits,
+            SectionFlags::ALLOC,
+            64,
+            b"MIT",
+        )
+        .unwrap();
+    assert!(matches!(
+        patcher.add_section(
+            c".elb.invalid",
+            SectionKind::ProgramBits,
+            SectionFlags::empty(),
+            3,
+            b"",
+        ),
+        Err(Error::InvalidAlign(3))
+    ));
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    assert_eq!(
+        Some(br#"{"name":"elb"}"#.to_vec()),
+        elf.read_section(c".elb.sbom", &names, &mut cursor).unwrap()
+    );
+    assert_eq!(
+        Some(b"MIT".to_vec()),
+        elf.read_section(c".elb.license", &names, &mut cursor)
+            .unwrap()
+    );
+    let section = elf
+        .sections
+        .iter()
+        .find(|section| names.get_string(section.name_offset as usize) == Some(c".elb.license"))
+        .unwrap();
+    assert_eq!(64, section.align);
+    assert_eq!(0, section.virtual_address % 64);
+    assert!(elf.segments.iter().any(|segment| {
+        segment.kind == SegmentKind::Loadable
+            && segment.file_offset_range().contains(&section.offset)
+            && segment
+                .virtual_address_range()
+                .contains(&section.virtual_address)
+    }));
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn add_nobits_section() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let len = bytes.len();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    assert!(matches!(
+        patcher.add_section(
+            c".elb.bss",
+            SectionKind::NoBits,
+            SectionFlags::ALLOC | SectionFlags::WRITE,
+            8,
+            &[0_u8; 100_000],
+        ),
+        Err(Error::InvalidSectionKind(8))
+    ));
+    assert!(matches!(
+        patcher.add_section(c".elb.null", SectionKind::Null, SectionFlags::ALLOC, 8, b""),
+        Err(Error::InvalidSectionKind(0))
+    ));
+    let i = patcher
+        .add_nobits_section(
+            c".elb.bss",
+            SectionFlags::ALLOC | SectionFlags::WRITE,
+            8,
+            100_000,
+        )
+        .unwrap();
+    let section = patcher.elf().sections[i].clone();
+    let cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor.clone(), PAGE_SIZE).unwrap();
+    // The section is covered by a writable LOAD segment.
+    assert!(elf.segments.iter().any(|segment| {
+        segment.kind == SegmentKind::Loadable
+            && segment.flags.contains(SegmentFlags::WRITABLE)
+            && segment.virtual_address <= section.virtual_address
+            && section.virtual_address + section.size
+                <= segment.virtual_address + segment.memory_size
+    }));
+    // The file didn't grow by the size of the section.
+    let bytes = cursor.into_inner();
+    assert!(bytes.len() < len + 100_000);
+    check_runs(bytes);
+}
+
+#[test]
+fn remove_section() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let num_sections = elf.sections.len();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher
+        .add_section(
+            c".elb.metadata",
+            SectionKind::ProgramBits,
+            SectionFlags::empty(),
+            1,
+            b"metadata",
+        )
+        .unwrap();
+    // Referenced sections can't be removed.
+    assert!(matches!(
+        patcher.remove_section(c".dynstr"),
+        Err(Error::SectionInUse(..))
+    ));
+    assert!(matches!(
+        patcher.remove_section(c".text"),
+        Err(Error::SectionInUse(..))
+    ));
+    patcher.remove_section(c".elb.metadata").unwrap();
+    patcher.remove_section(c".comment").unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    for name in [c".elb.metadata", c".comment"] {
+        assert_eq!(None, elf.read_section(name, &names, &mut cursor).unwrap());
+    }
+    assert!(elf.sections.len() < num_sections);
+    // Links point to the same sections.
+    let dynsym = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::DynamicSymbolTable)
+        .unwrap();
+    assert_eq!(
+        Some(c".dynstr"),
+        names.get_string(elf.sections[dynsym.link as usize].name_offset as usize)
+    );
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn strip() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    assert!(elf
+        .read_section(c".debug_info", &names, &mut cursor)
+        .unwrap()
+        .is_some());
+    let num_sections = elf.sections.len();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    // Debug file.
+    let mut debug_file = Cursor::new(Vec::new());
+    patcher.write_debug_file(&mut debug_file).unwrap();
+    let debug_elf = Elf::read(&mut debug_file, PAGE_SIZE).unwrap();
+    assert_eq!(num_sections, debug_elf.sections.len());
+    let debug_names = debug_elf
+        .read_section_names(&mut debug_file)
+        .unwrap()
+        .unwrap();
+    assert!(debug_elf
+        .read_section(c".debug_info", &debug_names, &mut debug_file)
+        .unwrap()
+        .is_some());
+    assert!(debug_elf
+        .sections
+        .iter()
+        .filter(|section| section.flags.contains(SectionFlags::ALLOC))
+        .all(|section| matches!(section.kind, SectionKind::NoBits | SectionKind::Note)));
+    // Strip debug.
+    patcher.strip_debug().unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    assert_eq!(
+        None,
+        elf.read_section(c".debug_info", &names, &mut cursor)
+            .unwrap()
+    );
+    assert!(elf
+        .read_section(c".symtab", &names, &mut cursor)
+        .unwrap()
+        .is_some());
+    // Strip all.
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.strip_all().unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    for name in [c".symtab", c".strtab"] {
+        assert_eq!(None, elf.read_section(name, &names, &mut cursor).unwrap());
+    }
+    assert!(elf
+        .sections
+        .iter()
+        .all(|section| section.kind != SectionKind::SymbolTable));
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn remove_section_from_relocatable() {
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    fs_err::write(dir.join("obj.s"), GROUP_OBJ).unwrap();
+    fs_err::write(
+        dir.join("main.c"),
+        "extern char elb; extern char* elb_ptr; int main() { return elb_ptr != &elb; }\n",
+    )
+    .unwrap();
+    let cc = |args: &[&str]| {
+        let status = Command::new("cc")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    cc(&["-c", "-o", "obj.o", "obj.s"]);
+    let bytes = fs_err::read(dir.join("obj.o")).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    // The section symbol is used by the relocations.
+    assert!(matches!(
+        patcher.remove_section(c".elb.info"),
+        Err(Error::SectionInUse(_, "symbol"))
+    ));
+    // Precedes the group members and defines a local symbol.
+    patcher.remove_section(c".elb.note").unwrap();
+    // Group member.
+    patcher.remove_section(c".elb.extra").unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    let section_name = |i: u32| names.get_string(elf.sections[i as usize].name_offset as usize);
+    let symtab = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::SymbolTable)
+        .unwrap();
+    let symbols: SymbolTable = elf.read_section_content(symtab, &mut cursor).unwrap();
+    let strings: StringTable = elf
+        .read_section_content(&elf.sections[symtab.link as usize], &mut cursor)
+        .unwrap();
+    let symbol_name = |i: u32| strings.get_string(symbols[i as usize].name_offset as usize);
+    assert!((0..symbols.len() as u32).all(|i| symbol_name(i) != Some(c"note_start")));
+    assert_eq!(
+        symbols.len(),
+        symtab.size as usize / elf.header.class.symbol_len()
+    );
+    assert_eq!(
+        symbols
+            .iter()
+            .take_while(|symbol| symbol.binding == SymbolBinding::Local)
+            .count(),
+        symtab.info as usize
+    );
+    for symbol in symbols.iter() {
+        match strings.get_string(symbol.name_offset as usize).unwrap() {
+            name if name == c"elb" => assert_eq!(
+                Some(c".text.elb"),
+                section_name(symbol.section_index.into())
+            ),
+            name if name == c"elb_ptr" => assert_eq!(
+                Some(c".data.elb"),
+                section_name(symbol.section_index.into())
+            ),
+            _ => {}
+        }
+    }
+    // Group members and signature.
+    let group = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::Group)
+        .unwrap();
+    let entries: Vec<u8> = elf.read_section_content(group, &mut cursor).unwrap();
+    let members: Vec<&CStr> = entries
+        .chunks_exact(4)
+        .skip(1)
+        .map(|entry| section_name(u32::from_ne_bytes(entry.try_into().unwrap())).unwrap())
+        .collect();
+    assert_eq!(vec![c".text.elb", c".data.elb", c".rela.data.elb"], members);
+    assert_eq!(Some(c"elb"), symbol_name(group.info));
+    // Relocations point to the same symbols.
+    let read_symbol_index = |cursor: &mut Cursor<Vec<u8>>, name: &CStr| {
+        let rela = elf
+            .sections
+            .iter()
+            .find(|section| names.get_string(section.name_offset as usize) == Some(name))
+            .unwrap();
+        let table: RelaTable = elf.read_section_content(rela, cursor).unwrap();
+        table[0].rel.symbol as usize
+    };
+    let symbol = &symbols[read_symbol_index(&mut cursor, c".rela.data.elb")];
+    assert_eq!(
+        Some(c"elb"),
+        strings.get_string(symbol.name_offset as usize)
+    );
+    let symbol = &symbols[read_symbol_index(&mut cursor, c".rela.elb.refs")];
+    assert_eq!(
+        Some(c".elb.info"),
+        section_name(symbol.section_index.into())
+    );
+    fs_err::write(dir.join("obj.o"), cursor.into_inner()).unwrap();
+    cc(&["-o", "main", "main.c", "obj.o"]);
+    let status = Command::new(dir.join("main")).status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn write_debug_file_with_invalid_section_size() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let mut elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let section = elf
+        .sections
+        .iter_mut()
+        .find(|section| {
+            section.kind == SectionKind::ProgramBits && !section.flags.contains(SectionFlags::ALLOC)
+        })
+        .unwrap();
+    section.size = u64::MAX / 2;
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    let mut debug_file = Cursor::new(Vec::new());
+    assert!(matches!(
+        patcher.write_debug_file(&mut debug_file),
+        Err(Error::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn set_debuglink() {
+    let workdir = tempfile::tempdir().unwrap();
+    let debug_path = workdir.path().join("patch.debug");
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    let mut debug_file = Cursor::new(Vec::new());
+    patcher.write_debug_file(&mut debug_file).unwrap();
+    let debug_bytes = debug_file.into_inner();
+    fs_err::write(&debug_path, &debug_bytes).unwrap();
+    patcher.strip_debug().unwrap();
+    // Add and then replace the link.
+    patcher
+        .replace_debuglink(&DebugLink {
+            file_name: c"other.debug".into(),
+            crc: 0,
+        })
+        .unwrap();
+    patcher.set_debuglink(&debug_path).unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let expected = DebugLink {
+        file_name: c"patch.debug".into(),
+        crc: debuglink_crc32(0, &debug_bytes),
+    };
+    assert_eq!(Some(expected), elf.read_debuglink(&mut cursor).unwrap());
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    let sections: Vec<_> = elf
+        .sections
+        .iter()
+        .filter(|section| names.get_string(section.name_offset as usize) == Some(c".gnu_debuglink"))
+        .collect();
+    assert_eq!(1, sections.len());
+    assert_eq!(4, sections[0].align);
+    assert_eq!(0, sections[0].offset % 4);
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn compact() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let original_len = bytes.len();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher
+        .add_section(
+            c".elb",
+            SectionKind::ProgramBits,
+            SectionFlags::ALLOC,
+            8,
+            &[1_u8; 10000],
+        )
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.remove_section(c".elb").unwrap();
+    patcher.strip_all().unwrap();
+    patcher.compact().unwrap();
+    let mut cursor = patcher.finish_and_truncate().unwrap();
+    assert!(cursor.get_ref().len() < original_len);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    elf.check().unwrap();
+    for segment in elf
+        .segments
+        .iter()
+        .filter(|segment| segment.kind == SegmentKind::Loadable)
+    {
+        let range = segment.virtual_address_range();
+        assert!(
+            segment.offset == 0
+                || elf.segments.iter().any(|other| {
+                    other.kind == SegmentKind::ProgramHeader
+                        && range.contains(&other.virtual_address)
+                })
+                || elf.sections.iter().any(|section| {
+                    section.flags.contains(SectionFlags::ALLOC)
+                        && range.contains(&section.virtual_address)
+                }),
+            "unused LOAD segment {segment:?}"
+        );
+    }
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn patch_in_place() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes.clone());
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_patch_policy(PatchPolicy::InPlaceOnly);
+    let interpreter = patcher.read_interpreter().unwrap().unwrap();
+    // The same value.
+    patcher.set_interpreter(&interpreter).unwrap();
+    // Longer value.
+    let mut longer = interpreter.clone().into_bytes();
+    longer.push(b'x');
+    assert!(matches!(
+        patcher.set_interpreter(&CString::new(longer).unwrap()),
+        Err(Error::DoesNotFit(..))
+    ));
+    // No RUNPATH to overwrite.
+    assert!(matches!(
+        patcher.set_dynamic_tag(DynamicTag::Runpath, c"/usr/local/lib"),
+        Err(Error::DoesNotFit(..))
+    ));
+    let cursor = patcher.finish().unwrap();
+    assert_eq!(bytes, cursor.get_ref()[..]);
+    // Relocate if the value doesn't fit.
+    let mut cursor = cursor;
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_patch_policy(PatchPolicy::PreferInPlace);
+    patcher
+        .set_dynamic_tag(DynamicTag::Runpath, c"/usr/local/lib")
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let old_bytes = cursor.get_ref().clone();
+    // Overwrite in place.
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_patch_policy(PatchPolicy::InPlaceOnly);
+    patcher
+        .set_dynamic_tag(DynamicTag::Rpath, c"/usr/lib")
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let new_bytes = cursor.get_ref();
+    assert_eq!(old_bytes.len(), new_bytes.len());
+    let num_changed = old_bytes
+        .iter()
+        .zip(new_bytes.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    // String + dynamic table entry tag.
+    assert!(num_changed <= "/usr/local/lib".len() + 1, "{num_changed}");
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let dynamic_table = elf.read_dynamic_table(&mut cursor).unwrap().unwrap();
+    let dynstr_table = elf.read_dynamic_string_table(&mut cursor).unwrap().unwrap();
+    assert_eq!(None, dynamic_table.get(DynamicTag::Runpath));
+    let rpath = dynamic_table.get(DynamicTag::Rpath).unwrap();
+    assert_eq!(Some(c"/usr/lib"), dynstr_table.get_string(rpath as usize));
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn patch_in_place_without_phdr_segment() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let mut elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    // Move the program header right after the last section, outside of any segment.
+    elf.segments
+        .retain(|segment| segment.kind != SegmentKind::ProgramHeader);
+    elf.header.num_segments = elf.segments.len() as u16;
+    elf.header.program_header_offset = elf.header.section_header_offset;
+    elf.header.section_header_offset += 16 * PAGE_SIZE;
+    elf.write(&mut cursor).unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    // Keep the number of sections, otherwise the headers are moved anyway.
+    let content = vec![0xab_u8; 2 * PAGE_SIZE as usize];
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_patch_policy(PatchPolicy::PreferInPlace);
+    patcher.remove_section(c".comment").unwrap();
+    patcher
+        .add_section(
+            c".elb",
+            SectionKind::ProgramBits,
+            SectionFlags::empty(),
+            1,
+            &content,
+        )
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    // The program header doesn't overwrite the new section.
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let names = elf.read_section_names(&mut cursor).unwrap().unwrap();
+    assert_eq!(
+        Some(content),
+        elf.read_section(c".elb", &names, &mut cursor).unwrap()
+    );
+}
+
+#[test]
+fn dry_run() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut file = DryRun::new(Cursor::new(bytes.clone()));
+    let elf = Elf::read(&mut file, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, file);
+    patcher
+        .add_section(
+            c".elb",
+            SectionKind::ProgramBits,
+            SectionFlags::empty(),
+            1,
+            b"hello",
+        )
+        .unwrap();
+    patcher
+        .set_dynamic_tag(DynamicTag::Runpath, c"/usr/local/lib")
+        .unwrap();
+    let mut file = patcher.finish().unwrap();
+    let plan = file.plan(PAGE_SIZE).unwrap();
+    assert!(!plan.writes.is_empty());
+    assert!(plan.new_len > plan.old_len);
+    let changes = plan.diff.changes();
+    assert!(changes.iter().any(
+        |change| matches!(change, ElfChange::SectionAdded(name, ..) if name.as_c_str() == c".elb")
+    ));
+    assert!(changes
+        .iter()
+        .any(|change| matches!(change, ElfChange::DynamicAdded(DynamicTag::Runpath, ..))));
+    assert_eq!(plan.header.section_header_offset, {
+        let elf = Elf::read(&mut file, PAGE_SIZE).unwrap();
+        elf.header.section_header_offset
+    });
+    // The original file is not modified.
+    assert_eq!(bytes, file.into_inner().into_inner());
+}
+
+#[test]
+fn rename_dynamic_symbols() {
+    let workdir = tempfile::tempdir().unwrap();
+    let dir = workdir.path();
+    // Many symbols to have multiple hash buckets.
+    let mut lib = String::new();
+    let mut main = String::from("#include <stdio.h>\n");
+    let mut calls = String::new();
+    for i in 0..NUM_SYMBOLS {
+        lib.push_str(&format!("int elb_{i}(void) {{ return {i}; }}\n"));
+        main.push_str(&format!("int elb_{i}(void);\n"));
+        calls.push_str(&format!("sum += elb_{i}();\n"));
+    }
+    main.push_str(&format!(
+        "int main() {{ int sum = 0; {calls} printf(\"%d\\n\", sum); return 0; }}\n"
+    ));
+    fs_err::write(dir.join("lib.c"), lib).unwrap();
+    fs_err::write(dir.join("main.c"), main).unwrap();
+    let cc = |args: &[&str]| {
+        let status = Command::new("cc")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    cc(&[
+        "-shared",
+        "-fPIC",
+        "-Wl,--hash-style=both",
+        "-o",
+        "libelb.so",
+        "lib.c",
+    ]);
+    cc(&[
+        "-Wl,--hash-style=both",
+        "-o",
+        "main",
+        "main.c",
+        "-L.",
+        "-lelb",
+    ]);
+    let mapping: BTreeMap<CString, CString> = (0..NUM_SYMBOLS)
+        .step_by(3)
+        .map(|i| {
+            (
+                CString::new(format!("elb_{i}")).unwrap(),
+                CString::new(format!("vendored_elb_{i}")).unwrap(),
+            )
+        })
+        .collect();
+    for name in ["libelb.so", "main"] {
+        let path = dir.join(name);
+        let mut cursor = Cursor::new(fs_err::read(&path).unwrap());
+        let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+        let mut patcher = ElfPatcher::new(elf, cursor);
+        patcher.rename_dynamic_symbols(&mapping).unwrap();
+        let mut cursor = patcher.finish().unwrap();
+        let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+        let names = read_dynamic_symbol_names(&elf, &mut cursor);
+        for (old, new) in mapping.iter() {
+            assert!(!names.contains(old));
+            assert!(names.contains(new));
+        }
+        fs_err::write(&path, cursor.into_inner()).unwrap();
+    }
+    let output = Command::new(dir.join("main"))
+        .env("LD_LIBRARY_PATH", dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let expected: u32 = (0..NUM_SYMBOLS).sum();
+    assert_eq!(format!("{expected}\n").as_bytes(), output.stdout);
+}
+
+#[test]
+fn set_symbol_binding_and_visibility() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher
+        .set_symbol_binding(c"main", SymbolBinding::Local)
+        .unwrap();
+    patcher
+        .set_symbol_binding(c"rust_eh_personality", SymbolBinding::Weak)
+        .unwrap();
+    patcher
+        .set_symbol_visibility(c"rust_eh_personality", SymbolVisibility::Hidden)
+        .unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let symtab = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::SymbolTable)
+        .unwrap();
+    let symbols: SymbolTable = symtab
+        .read_content(&mut cursor, elf.header.class, elf.header.byte_order)
+        .unwrap();
+    let strings: StringTable = elf.sections[symtab.link as usize]
+        .read_content(&mut cursor, elf.header.class, elf.header.byte_order)
+        .unwrap();
+    // Local symbols preceed the others.
+    let num_locals = symtab.info as usize;
+    assert!(symbols[..num_locals]
+        .iter()
+        .all(|symbol| symbol.binding == SymbolBinding::Local));
+    assert!(symbols[num_locals..]
+        .iter()
+        .all(|symbol| symbol.binding != SymbolBinding::Local));
+    let find = |name: &CStr| {
+        symbols
+            .iter()
+            .find(|symbol| strings.get_string(symbol.name_offset as usize) == Some(name))
+            .unwrap()
+    };
+    assert_eq!(SymbolBinding::Local, find(c"main").binding);
+    let symbol = find(c"rust_eh_personality");
+    assert_eq!(SymbolBinding::Weak, symbol.binding);
+    assert_eq!(SymbolVisibility::Hidden, symbol.visibility);
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn section_size_limit_is_checked() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let limits = Limits {
+        max_section_size: 16,
+        ..Default::default()
+    };
+    let elf = Elf::read_with_limits(&mut cursor, PAGE_SIZE, limits).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    assert!(matches!(
+        patcher.set_symbol_binding(c"main", SymbolBinding::Local),
+        Err(Error::LimitExceeded(..))
+    ));
+    let mapping = BTreeMap::from([(c"main".into(), c"elb_main".into())]);
+    assert!(matches!(
+        patcher.rename_dynamic_symbols(&mapping),
+        Err(Error::LimitExceeded(..))
+    ));
+}
+
+#[test]
+fn set_executable_stack() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_executable_stack(true).unwrap();
+    patcher.set_stack_size(16 * 1024 * 1024).unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let stack = gnu_stack_segment(&elf).unwrap();
+    assert!(stack.flags.contains(SegmentFlags::EXECUTABLE));
+    assert_eq!(16 * 1024 * 1024, stack.memory_size);
+    // Remove the segment and check that it's added back.
+    let mut elf = elf;
+    elf.segments
+        .retain(|segment| segment.kind != SegmentKind::GNU_STACK);
+    let num_segments = elf.segments.len();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_executable_stack(false).unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let stack = gnu_stack_segment(&elf).unwrap();
+    assert!(!stack.flags.contains(SegmentFlags::EXECUTABLE));
+    assert!(elf.segments.len() > num_segments);
+    check_runs(cursor.into_inner());
+}
+
+#[test]
+fn set_header_fields() {
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    let entry_point = elf.header.entry_point;
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_os_abi(OsAbi::Freebsd).unwrap();
+    patcher.set_abi_version(3).unwrap();
+    assert!(matches!(
+        patcher.set_entry_point(u64::MAX),
+        Err(Error::InvalidEntryPoint(u64::MAX))
+    ));
+    assert!(matches!(
+        patcher.set_flags(ArmFlags::HARD_FLOAT, true),
+        Err(Error::MachineMismatch(Machine::Arm, _))
+    ));
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    assert_eq!(OsAbi::Freebsd, elf.header.os_abi);
+    assert_eq!(3, elf.header.abi_version);
+    assert_eq!(entry_point, elf.header.entry_point);
+    // Restore the original values.
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_os_abi(OsAbi::Sysv).unwrap();
+    patcher.set_abi_version(0).unwrap();
+    patcher.set_entry_point(entry_point).unwrap();
+    check_runs(patcher.finish().unwrap().into_inner());
+    // Architecture-specific flags.
+    let bytes = fs_err::read(std::env::current_exe().unwrap()).unwrap();
+    let mut cursor = Cursor::new(bytes);
+    let mut elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    elf.header.machine = Machine::Arm;
+    elf.header.flags = ArmFlags::SOFT_FLOAT.bits();
+    let mut patcher = ElfPatcher::new(elf, cursor);
+    patcher.set_flags(ArmFlags::SOFT_FLOAT, false).unwrap();
+    patcher.set_flags(ArmFlags::HARD_FLOAT, true).unwrap();
+    let mut cursor = patcher.finish().unwrap();
+    let elf = Elf::read(&mut cursor, PAGE_SIZE).unwrap();
+    assert_eq!(ArmFlags::HARD_FLOAT.bits(), elf.header.flags);
+}
+
+/// Check that the patched file still runs.
+fn check_runs(bytes: Vec<u8>) {
+    let workdir = tempfile::tempdir().unwrap();
+    let path = workdir.path().join("patched");
+    fs_err::write(&path, bytes).unwrap();
+    fs_err::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let output = Command::new(&path).arg("--list").output().unwrap();
+    assert!(output.status.success());
+}
+
+fn gnu_stack_segment(elf: &Elf) -> Option<&Segment> {
+    elf.segments
+        .iter()
+        .find(|segment| segment.kind == SegmentKind::GNU_STACK)
+}
+
+fn dynamic_section(elf: &Elf) -> elb::Section {
+    elf.sections
+        .iter()
+        .find(|section| section.kind == SectionKind::Dynamic)
+        .cloned()
+        .unwrap()
+}
+
+fn read_needed(elf: &Elf, cursor: &mut Cursor<Vec<u8>>) -> Vec<CString> {
+    let Some(table) = elf.read_dynamic_table(cursor).unwrap() else {
+        return Vec::new();
+    };
+    let strings = elf.read_dynamic_string_table(cursor).unwrap().unwrap();
+    table
+        .iter()
+        .filter(|(tag, _)| *tag == DynamicTag::Needed)
+        .map(|(_, offset)| {
+            let name: &CStr = strings.get_string(*offset as usize).unwrap();
+            name.into()
+        })
+        .collect()
+}
+
+fn read_dynamic_symbol_names(elf: &Elf, cursor: &mut Cursor<Vec<u8>>) -> Vec<CString> {
+    let dynsym = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SectionKind::DynamicSymbolTable)
+        .unwrap();
+    let symbols: SymbolTable = dynsym
+        .read_content(cursor, elf.header.class, elf.header.byte_order)
+        .unwrap();
+    let strings: StringTable = elf.sections[dynsym.link as usize]
+        .read_content(cursor, elf.header.class, elf.header.byte_order)
+        .unwrap();
+    symbols
+        .iter()
+        .map(|symbol| {
+            strings
+                .get_string(symbol.name_offset as usize)
+                .unwrap()
+                .into()
+        })
+        .collect()
+}
+
+const PAGE_SIZE: u64 = 4096;
+const NUM_SYMBOLS: u32 = 100;
+
+/// Relocatable object with a COMDAT section group.
+const GROUP_OBJ: &str = r#"
+.section .elb.note,"",%progbits
+note_start:
+.byte 1
+.section .elb.info,"",%progbits
+.byte 2
+.section .text.elb,"axG",%progbits,elb,comdat
+.globl elb
+elb:
+.byte 0
+.section .data.elb,"awG",%progbits,elb,comdat
+.globl elb_ptr
+elb_ptr:
+.quad elb
+.section .elb.extra,"G",%progbits,elb,comdat
+.byte 3
+.section .elb.refs,"",%progbits
+.quad .elb.info
+.section .note.GNU-stack,"",%progbits
+"#;
+
+/// Walks the dynamic table via the pointer that is initialized by the relative relocation.
+const DYNAMIC_MAIN: &str = r#"
+#include <link.h>
+#include <stdio.h>
+
+extern ElfW(Dyn) _DYNAMIC[];
+ElfW(Dyn)* dynamic = _DYNAMIC;
+
+int main() {
+    for (ElfW(Dyn)* entry = dynamic; entry->d_tag != DT_NULL; ++entry) {
+        if (entry->d_tag == DT_RUNPATH) {
+            printf("runpath\n");
+            return 0;
+        }
+    }
+    return 1;
+}
+"#;
@@ -0,0 +1,55 @@
+#![allow(missing_docs)]
+#![cfg(feature = "serde")]
+
+use fs_err::File;
+
+use elb::Elf;
+use elb::Header;
+use elb::ProgramHeader;
+use elb::SectionHeader;
+use elb::SegmentFlags;
+use elb::SegmentKind;
+
+#[test]
+fn serde_round_trip() {
+    let mut file = File::open(std::env::current_exe().unwrap()).unwrap();
+    let elf = Elf::read(&mut file, PAGE_SIZE).unwrap();
+    let header = serde_json::to_string(&elf.header).unwrap();
+    let segments = serde_json::to_string(&elf.segments).unwrap();
+    let sections = serde_json::to_string(&elf.sections).unwrap();
+    let header2: Header = serde_json::from_str(&header).unwrap();
+    let segments2: ProgramHeader = serde_json::from_str(&segments).unwrap();
+    let sections2: SectionHeader = serde_json::from_str(&sections).unwrap();
+    assert_eq!(header, serde_json::to_string(&header2).unwrap());
+    assert_eq!(segments, serde_json::to_string(&segments2).unwrap());
+    assert_eq!(sections, serde_json::to_string(&sections2).unwrap());
+    let mut file = File::open(std::env::current_exe().unwrap()).unwrap();
+    let dynamic_table = elf.read_dynamic_table(&mut file).unwrap().unwrap();
+    let dynamic_table_json = serde_json::to_string(&dynamic_table).unwrap();
+    let dynamic_table2: elb::DynamicTable = serde_json::from_str(&dynamic_table_json).unwrap();
+    assert_eq!(
+        dynamic_table_json,
+        serde_json::to_string(&dynamic_table2).unwrap()
+    );
+}
+
+#[test]
+fn names_are_kept_next_to_numbers() {
+    let json = serde_json::to_value(SegmentKind::Loadable).unwrap();
+    assert_eq!(serde_json::json!({"name": "Loadable", "value": 1}), json);
+    let json = serde_json::to_value(SegmentKind::Other(0x6474e551)).unwrap();
+    assert_eq!(serde_json::json!({"name": null, "value": 0x6474e551}), json);
+    let kind: SegmentKind = serde_json::from_value(json).unwrap();
+    assert_eq!(SegmentKind::Other(0x6474e551), kind);
+    let flags =
+        SegmentFlags::READABLE | SegmentFlags::EXECUTABLE | SegmentFlags::from_bits_retain(8);
+    let json = serde_json::to_value(flags).unwrap();
+    assert_eq!(
+        serde_json::json!({"names": ["EXECUTABLE", "READABLE"], "bits": 13}),
+        json
+    );
+    let flags2: SegmentFlags = serde_json::from_value(json).unwrap();
+    assert_eq!(flags, flags2);
+}
+
+const PAGE_SIZE: u64 = 4096;